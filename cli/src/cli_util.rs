@@ -489,6 +489,9 @@ pub struct WorkspaceCommandHelper {
     may_update_working_copy: bool,
     working_copy_shared_with_git: bool,
     path_converter: RepoPathUiConverter,
+    /// The repo whose working-copy commit is checked out, if intermediate
+    /// transactions have been committed since the working copy was updated.
+    checked_out_repo: Option<Arc<ReadonlyRepo>>,
 }
 
 impl WorkspaceCommandHelper {
@@ -525,6 +528,7 @@ impl WorkspaceCommandHelper {
             may_update_working_copy,
             working_copy_shared_with_git,
             path_converter,
+            checked_out_repo: None,
         };
         // Parse commit_summary template (and short-prefixes revset) early to
         // report error before starting mutable operation.
@@ -1101,15 +1105,38 @@ impl WorkspaceCommandHelper {
         repo: &dyn Repo,
         commits: impl IntoIterator<Item = &'a CommitId>,
     ) -> Result<(), CommandError> {
+        if let Some(commit_id) = self.find_repo_immutable_commits(repo, commits)?.first() {
+            let error = if commit_id == self.repo().store().root_commit_id() {
+                user_error(format!(
+                    "The root commit {} is immutable",
+                    short_commit_hash(commit_id),
+                ))
+            } else {
+                user_error_with_hint(
+                    format!("Commit {} is immutable", short_commit_hash(commit_id)),
+                    "Pass `--ignore-immutable` or configure the set of immutable commits via \
+                     `revset-aliases.immutable_heads()`.",
+                )
+            };
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the commits among `commits` which can't be rewritten, in reverse
+    /// topological order.
+    fn find_repo_immutable_commits<'a>(
+        &self,
+        repo: &dyn Repo,
+        commits: impl IntoIterator<Item = &'a CommitId>,
+    ) -> Result<Vec<CommitId>, CommandError> {
         if self.global_args.ignore_immutable {
             let root_id = self.repo().store().root_commit_id();
             return if commits.into_iter().contains(root_id) {
-                Err(user_error(format!(
-                    "The root commit {} is immutable",
-                    short_commit_hash(root_id),
-                )))
+                Ok(vec![root_id.clone()])
             } else {
-                Ok(())
+                Ok(vec![])
             };
         }
 
@@ -1131,27 +1158,10 @@ impl WorkspaceCommandHelper {
         );
         expression.intersect_with(&to_rewrite_revset);
 
-        let mut commit_id_iter = expression.evaluate_to_commit_ids().map_err(|e| {
+        let commit_id_iter = expression.evaluate_to_commit_ids().map_err(|e| {
             config_error_with_message("Invalid `revset-aliases.immutable_heads()`", e)
         })?;
-
-        if let Some(commit_id) = commit_id_iter.next() {
-            let error = if &commit_id == self.repo().store().root_commit_id() {
-                user_error(format!(
-                    "The root commit {} is immutable",
-                    short_commit_hash(&commit_id),
-                ))
-            } else {
-                user_error_with_hint(
-                    format!("Commit {} is immutable", short_commit_hash(&commit_id)),
-                    "Pass `--ignore-immutable` or configure the set of immutable commits via \
-                     `revset-aliases.immutable_heads()`.",
-                )
-            };
-            return Err(error);
-        }
-
-        Ok(())
+        Ok(commit_id_iter.collect())
     }
 
     pub fn check_rewritable<'a>(
@@ -1161,6 +1171,15 @@ impl WorkspaceCommandHelper {
         self.check_repo_rewritable(self.repo().as_ref(), commits)
     }
 
+    /// Returns the commits among `commits` which can't be rewritten, in reverse
+    /// topological order.
+    pub fn find_immutable_commits<'a>(
+        &self,
+        commits: impl IntoIterator<Item = &'a CommitId>,
+    ) -> Result<Vec<CommitId>, CommandError> {
+        self.find_repo_immutable_commits(self.repo().as_ref(), commits)
+    }
+
     #[instrument(skip_all)]
     fn snapshot_working_copy(&mut self, ui: &mut Ui) -> Result<(), CommandError> {
         let workspace_id = self.workspace_id().to_owned();
@@ -1346,12 +1365,17 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
             }
         }
 
-        let old_repo = tx.base_repo().clone();
+        // If intermediate transactions have been committed, the working copy
+        // still has the commit from before the first of them checked out.
+        let old_repo = self
+            .checked_out_repo
+            .take()
+            .unwrap_or_else(|| tx.base_repo().clone());
 
         let maybe_old_wc_commit = old_repo
             .view()
             .get_wc_commit_id(self.workspace_id())
-            .map(|commit_id| tx.base_repo().store().get_commit(commit_id))
+            .map(|commit_id| old_repo.store().get_commit(commit_id))
             .transpose()?;
         let maybe_new_wc_commit = tx
             .repo()
@@ -1393,6 +1417,25 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
         Ok(())
     }
 
+    /// Commits an intermediate transaction of a command which records its
+    /// changes as several operations. The working copy isn't updated until the
+    /// last transaction is finished by `finish_transaction()`.
+    fn finish_intermediate_transaction(
+        &mut self,
+        mut tx: Transaction,
+        description: impl Into<String>,
+    ) -> Result<(), CommandError> {
+        if !tx.mut_repo().has_changes() {
+            return Ok(());
+        }
+        tx.mut_repo().rebase_descendants(&self.settings)?;
+        if self.checked_out_repo.is_none() {
+            self.checked_out_repo = Some(tx.base_repo().clone());
+        }
+        self.user_repo = ReadonlyUserRepo::new(tx.commit(description));
+        Ok(())
+    }
+
     /// Inform the user about important changes to the repo since the previous
     /// operation (when `old_repo` was loaded).
     fn report_repo_changes(
@@ -1666,6 +1709,15 @@ impl WorkspaceCommandTransaction<'_> {
         self.helper.finish_transaction(ui, self.tx, description)
     }
 
+    /// Commits the transaction as one of several operations recorded by the
+    /// command, without updating the working copy. The command must finish its
+    /// last transaction with [`WorkspaceCommandTransaction::finish`] to update
+    /// the working copy.
+    pub fn finish_intermediate(self, description: impl Into<String>) -> Result<(), CommandError> {
+        self.helper
+            .finish_intermediate_transaction(self.tx, description)
+    }
+
     /// Returns the wrapped [`Transaction`] for circumstances where
     /// finer-grained control is needed. The caller becomes responsible for
     /// finishing the `Transaction`, including rebasing descendants and updating
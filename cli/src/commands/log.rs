@@ -13,16 +13,16 @@
 // limitations under the License.
 
 use jj_lib::backend::CommitId;
-use jj_lib::graph::{GraphEdgeType, ReverseGraphIterator, TopoGroupedGraphIterator};
+use jj_lib::graph::{ReverseGraphIterator, TopoGroupedGraphIterator};
 use jj_lib::repo::Repo;
 use jj_lib::revset::{RevsetExpression, RevsetFilterPredicate, RevsetIteratorExt};
 use tracing::instrument;
 
-use crate::cli_util::{format_template, CommandHelper, LogContentFormat, RevisionArg};
+use crate::cli_util::{CommandHelper, LogContentFormat, RevisionArg};
 use crate::command_error::CommandError;
 use crate::commit_templater::CommitTemplateLanguage;
 use crate::diff_util::DiffFormatArgs;
-use crate::graphlog::{get_graphlog, Edge};
+use crate::graphlog::write_commit_graph;
 use crate::ui::Ui;
 
 /// Show revision history
@@ -154,84 +154,28 @@ pub(crate) fn cmd_log(
         let limit = args.limit.or(args.deprecated_limit).unwrap_or(usize::MAX);
 
         if !args.no_graph {
-            let mut graph = get_graphlog(command.settings(), formatter.raw());
             let forward_iter = TopoGroupedGraphIterator::new(revset.iter_graph());
             let iter: Box<dyn Iterator<Item = _>> = if args.reversed {
                 Box::new(ReverseGraphIterator::new(forward_iter))
             } else {
                 Box::new(forward_iter)
             };
-            for (commit_id, edges) in iter.take(limit) {
-                // The graph is keyed by (CommitId, is_synthetic)
-                let mut graphlog_edges = vec![];
-                // TODO: Should we update revset.iter_graph() to yield this flag instead of all
-                // the missing edges since we don't care about where they point here
-                // anyway?
-                let mut has_missing = false;
-                let mut elided_targets = vec![];
-                for edge in edges {
-                    match edge.edge_type {
-                        GraphEdgeType::Missing => {
-                            has_missing = true;
-                        }
-                        GraphEdgeType::Direct => {
-                            graphlog_edges.push(Edge::Direct((edge.target, false)));
-                        }
-                        GraphEdgeType::Indirect => {
-                            if use_elided_nodes {
-                                elided_targets.push(edge.target.clone());
-                                graphlog_edges.push(Edge::Direct((edge.target, true)));
-                            } else {
-                                graphlog_edges.push(Edge::Indirect((edge.target, false)));
-                            }
-                        }
+            write_commit_graph(
+                ui,
+                command.settings(),
+                formatter,
+                store,
+                iter.take(limit),
+                &template,
+                &node_template,
+                use_elided_nodes,
+                |formatter, commit| {
+                    if let Some(renderer) = &diff_renderer {
+                        renderer.show_patch(ui, formatter, commit, matcher.as_ref())?;
                     }
-                }
-                if has_missing {
-                    graphlog_edges.push(Edge::Missing);
-                }
-                let mut buffer = vec![];
-                let key = (commit_id, false);
-                let commit = store.get_commit(&key.0)?;
-                with_content_format.write_graph_text(
-                    ui.new_formatter(&mut buffer).as_mut(),
-                    |formatter| template.format(&commit, formatter),
-                    || graph.width(&key, &graphlog_edges),
-                )?;
-                if !buffer.ends_with(b"\n") {
-                    buffer.push(b'\n');
-                }
-                if let Some(renderer) = &diff_renderer {
-                    let mut formatter = ui.new_formatter(&mut buffer);
-                    renderer.show_patch(ui, formatter.as_mut(), &commit, matcher.as_ref())?;
-                }
-
-                let node_symbol = format_template(ui, &Some(commit), &node_template);
-                graph.add_node(
-                    &key,
-                    &graphlog_edges,
-                    &node_symbol,
-                    &String::from_utf8_lossy(&buffer),
-                )?;
-                for elided_target in elided_targets {
-                    let elided_key = (elided_target, true);
-                    let real_key = (elided_key.0.clone(), false);
-                    let edges = [Edge::Direct(real_key)];
-                    let mut buffer = vec![];
-                    with_content_format.write_graph_text(
-                        ui.new_formatter(&mut buffer).as_mut(),
-                        |formatter| writeln!(formatter.labeled("elided"), "(elided revisions)"),
-                        || graph.width(&elided_key, &edges),
-                    )?;
-                    let node_symbol = format_template(ui, &None, &node_template);
-                    graph.add_node(
-                        &elided_key,
-                        &edges,
-                        &node_symbol,
-                        &String::from_utf8_lossy(&buffer),
-                    )?;
-                }
-            }
+                    Ok(())
+                },
+            )?;
         } else {
             let iter: Box<dyn Iterator<Item = CommitId>> = if args.reversed {
                 Box::new(revset.iter().reversed())
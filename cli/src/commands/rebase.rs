@@ -14,28 +14,43 @@
 
 use std::borrow::Borrow;
 use std::collections::{HashMap, HashSet};
-use std::io::Write;
+use std::io::{self, Write};
+use std::path::Path;
 use std::rc::Rc;
+use std::slice;
 use std::sync::Arc;
 
 use clap::ArgGroup;
-use indexmap::{IndexMap, IndexSet};
+use indexmap::IndexSet;
 use itertools::Itertools;
 use jj_lib::backend::CommitId;
 use jj_lib::commit::{Commit, CommitIteratorExt};
-use jj_lib::dag_walk;
+use jj_lib::git;
+use jj_lib::graph::TopoGroupedGraphIterator;
+use jj_lib::matchers::{EverythingMatcher, Matcher};
 use jj_lib::object_id::ObjectId;
-use jj_lib::repo::{MutableRepo, ReadonlyRepo, Repo};
+use jj_lib::op_store::{RefTarget, RemoteRefState};
+use jj_lib::repo::{ReadonlyRepo, Repo};
 use jj_lib::revset::{RevsetExpression, RevsetIteratorExt};
-use jj_lib::rewrite::{rebase_commit_with_options, CommitRewriter, EmptyBehaviour, RebaseOptions};
+use jj_lib::rewrite::{
+    move_commits, rebase_commit_with_options, restore_tree, CommitRewriter, EmptyBehaviour,
+    MoveCommitsStats, RebaseOptions, RebasePlan, RebasedCommit,
+};
 use jj_lib::settings::UserSettings;
 use tracing::instrument;
 
 use crate::cli_util::{
-    short_commit_hash, CommandHelper, RevisionArg, WorkspaceCommandHelper,
-    WorkspaceCommandTransaction,
+    edit_temp_file, short_change_hash, short_commit_hash, CommandHelper, RevisionArg,
+    WorkspaceCommandHelper, WorkspaceCommandTransaction,
+};
+use crate::command_error::{
+    cli_error, internal_error, user_error, user_error_with_hint, user_error_with_message,
+    CommandError,
 };
-use crate::command_error::{cli_error, user_error, CommandError};
+use crate::commit_templater::CommitTemplateLanguage;
+use crate::description_util::combine_messages;
+use crate::formatter::PlainTextFormatter;
+use crate::graphlog::write_commit_graph;
 use crate::ui::Ui;
 
 /// Move revisions to different parent(s)
@@ -125,8 +140,10 @@ use crate::ui::Ui;
 /// commit. This is true in general; it is not specific to this command.
 #[derive(clap::Args, Clone, Debug)]
 #[command(verbatim_doc_comment)]
-#[command(group(ArgGroup::new("to_rebase").args(&["branch", "source", "revisions"])))]
-#[command(group(ArgGroup::new("target").args(&["destination", "insert_after", "insert_before"]).multiple(true).required(true)))]
+#[command(group(ArgGroup::new("to_rebase").args(&["branch", "source", "revisions", "targets_file"])))]
+#[command(group(ArgGroup::new("target").args(&["destination", "insert_after", "insert_before", "auto_destination", "interactive"]).multiple(true).required(true)))]
+#[command(group(ArgGroup::new("revisions_or_targets_file").args(&["revisions", "targets_file"])))]
+#[command(group(ArgGroup::new("source_or_targets_file").args(&["source", "targets_file"])))]
 pub(crate) struct RebaseArgs {
     /// Rebase the whole branch relative to destination's ancestors (can be
     /// repeated)
@@ -157,47 +174,336 @@ pub(crate) struct RebaseArgs {
     /// If none of `-b`, `-s`, or `-r` is provided, then the default is `-b @`.
     #[arg(long, short)]
     revisions: Vec<RevisionArg>,
+    /// Don't move these revisions along with the `-s` or `-b` revisions
+    ///
+    /// The skipped revisions are rebased onto the nearest ancestors which are
+    /// not moved, like the descendants of `-r` revisions. The moved revisions
+    /// which were on top of them are rebased onto their nearest moved
+    /// ancestors.
+    #[arg(long, value_name = "REVSETS", conflicts_with = "revisions")]
+    skip: Vec<RevisionArg>,
+    /// Read the revisions to rebase from a file, one revset per line
+    ///
+    /// Blank lines and lines starting with `#` are ignored. The revsets are
+    /// used as if they were passed to `-r`, `-s`, or `-b`, as selected by
+    /// `--targets-mode`. Flags which only work with one of `-r`, `-s`, or `-b`
+    /// only work with the corresponding mode.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = clap::ValueHint::FilePath,
+        requires = "targets_mode"
+    )]
+    targets_file: Option<String>,
+    /// How to rebase the revisions read from `--targets-file`
+    #[arg(long, value_enum, requires = "targets_file")]
+    targets_mode: Option<TargetsMode>,
     /// The revision(s) to rebase onto (can be repeated to create a merge
     /// commit)
+    ///
+    /// A destination which is already a parent of a rebased commit is kept as
+    /// a parent exactly once, and the other destinations are added as
+    /// additional parents.
+    ///
+    /// The new parents are in the order the destinations are given, so the
+    /// first destination becomes the first parent. Leaving out a repeated or
+    /// redundant destination doesn't change the order of the others.
     #[arg(long, short)]
     destination: Vec<RevisionArg>,
+    /// Rebase onto the `trunk()` revision
+    ///
+    /// `trunk()` is configured by the `revset-aliases."trunk()"` setting, and
+    /// defaults to the `main`, `master`, or `trunk` branch of the `origin` or
+    /// `upstream` remote. It is an error if no such branch exists.
+    #[arg(
+        long,
+        conflicts_with = "destination",
+        conflicts_with = "insert_after",
+        conflicts_with = "insert_before"
+    )]
+    auto_destination: bool,
     /// The revision(s) to insert after (can be repeated to create a merge
     /// commit)
     ///
-    /// Only works with `-r`.
+    /// With `-s` or `-b`, the revisions are inserted together with their
+    /// descendants, and the children of the given revisions are rebased onto
+    /// the heads of the inserted commits.
     #[arg(
         long,
         short = 'A',
         visible_alias = "after",
-        conflicts_with = "destination",
-        conflicts_with = "source",
-        conflicts_with = "branch"
+        conflicts_with = "destination"
     )]
     insert_after: Vec<RevisionArg>,
     /// The revision(s) to insert before (can be repeated to create a merge
     /// commit)
     ///
-    /// Only works with `-r`.
+    /// The rebased commits are inserted on the edge between each given
+    /// revision and its first parent, and become its first parent in turn. If
+    /// a given revision is a merge commit, its other parents are kept after
+    /// the rebased commits, in their original order.
+    ///
+    /// With `-s` or `-b`, the revisions are inserted together with their
+    /// descendants, and the given revisions are rebased onto the heads of the
+    /// inserted commits.
     #[arg(
         long,
         short = 'B',
         visible_alias = "before",
-        conflicts_with = "destination",
-        conflicts_with = "source",
-        conflicts_with = "branch"
+        conflicts_with = "destination"
     )]
     insert_before: Vec<RevisionArg>,
+    /// Choose the new parents of each revision in an editor
+    ///
+    /// The editor lists the revisions to rebase, parents first, each on a line
+    /// with its change id followed by its current parents. Edit the parents to
+    /// move each revision onto its own new parents. A parent can also be one
+    /// of the other revisions being rebased. Removing a line leaves the parents
+    /// of that revision unchanged.
+    ///
+    /// Only works with `-r`.
+    #[arg(
+        long,
+        short,
+        requires = "revisions_or_targets_file",
+        conflicts_with_all = ["destination", "insert_after", "insert_before", "auto_destination"]
+    )]
+    interactive: bool,
+
+    /// Move the descendants of the revisions along with them
+    ///
+    /// By default, the descendants of the revisions are rebased onto the
+    /// parents of the revisions to fill the hole left behind. With this flag,
+    /// they stay attached to the revisions instead, as with `-s`. For example,
+    /// `jj rebase -r K -d M --move-descendants` would rebase L onto K' in the
+    /// example above.
+    ///
+    /// Only works with `-r`.
+    #[arg(
+        long,
+        requires = "revisions_or_targets_file",
+        conflicts_with = "interactive"
+    )]
+    move_descendants: bool,
 
-    /// Deprecated. Use --skip-emptied instead.
-    #[arg(long, conflicts_with = "revisions", hide = true)]
+    /// Print the old and new parents of each commit which would be moved,
+    /// without rebasing anything
+    ///
+    /// This shows how `--insert-after` and `--insert-before` splice the
+    /// revisions into the graph, including the new parents of the commits
+    /// they are inserted before.
+    ///
+    /// Only works with `-r`, `--insert-after`, or `--insert-before`.
+    #[arg(
+        long,
+        conflicts_with_all = ["show_mapping", "show_result", "fold_trivial", "split_paths", "atomic_steps"]
+    )]
+    explain: bool,
+
+    /// Deprecated. Use --empty=drop-newly-empty instead.
+    #[arg(long, hide = true)]
     skip_empty: bool,
 
     /// If true, when rebasing would produce an empty commit, the commit is
     /// abandoned. It will not be abandoned if it was already empty before the
     /// rebase. Will never skip merge commits with multiple non-empty
     /// parents.
-    #[arg(long, conflicts_with = "revisions")]
+    ///
+    /// This is equivalent to `--empty=drop-newly-empty`.
+    #[arg(long)]
     skip_emptied: bool,
+
+    /// What to do with commits which are empty after the rebase
+    ///
+    /// Merge commits with multiple non-empty parents are never abandoned.
+    ///
+    /// With `-r`, only the rebased revisions are abandoned. Their descendants
+    /// are kept even if they become empty.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        conflicts_with_all = ["skip_empty", "skip_emptied"]
+    )]
+    empty: Option<EmptyMode>,
+
+    /// Keep the author timestamp of the rebased commits
+    ///
+    /// By default, the author timestamp of a rebased commit which is empty and
+    /// has no description is reset to the current time. Commits which are
+    /// already in place aren't rewritten at all, so they always keep their
+    /// author timestamp.
+    #[arg(long)]
+    keep_author_timestamp: bool,
+
+    /// Print the new commit id of each rebased commit
+    ///
+    /// Each rebased commit is printed as `OLD -> NEW` on its own line, or as
+    /// `OLD -> (abandoned)` if it was abandoned.
+    #[arg(long, conflicts_with_all = ["fold_trivial", "reorder", "split_paths"])]
+    show_mapping: bool,
+
+    /// Show a graph of the rebased commits and their new parents
+    ///
+    /// After the rebase, the rebased commits are rendered like `jj log` would,
+    /// so the resulting topology can be checked at a glance. Abandoned commits
+    /// are not shown.
+    #[arg(
+        long,
+        conflicts_with_all = ["fold_trivial", "reorder", "split_paths", "atomic_steps"]
+    )]
+    show_result: bool,
+
+    /// Fold rebased commits touching at most this many paths into their parent
+    ///
+    /// After the rebase, a rebased commit with a single parent which was also
+    /// rebased is squashed into that parent if its changes touch no more than
+    /// `MAX_PATHS` paths. Only the number of changed paths is counted, not
+    /// the size of the changes to them. Working-copy commits are never folded,
+    /// and a commit is not folded into a parent which is itself folded or which
+    /// already received another folded commit.
+    ///
+    /// If both the commit and its parent have a description, an editor is
+    /// opened to combine them, like `jj squash` does.
+    #[arg(long, value_name = "MAX_PATHS")]
+    fold_trivial: Option<usize>,
+
+    /// Don't fail if `-r` or `-b` select no commits to rebase
+    ///
+    /// By default, rebasing nothing is an error, since it usually means that a
+    /// revset was mistyped.
+    #[arg(long)]
+    allow_empty_selection: bool,
+
+    /// Arrange the given revisions in a linear chain after rebasing
+    /// (comma-separated, can be repeated)
+    ///
+    /// The revisions are listed from parent to child. Each following revision
+    /// is moved onto the previous one as if by `-r`, so the children of a moved
+    /// revision are rebased onto its parent, and the first revision can end up
+    /// lower in the chain than it was. For example, `--reorder d,c,b` on the
+    /// chain `b -> c -> d` reverses it, so that `d` becomes the child of the
+    /// parent of `b`. All revisions must be part of the rebased subtree, and
+    /// merge commits can't be reordered.
+    ///
+    /// Only works with `-s`.
+    #[arg(long, value_delimiter = ',', requires = "source_or_targets_file")]
+    reorder: Vec<RevisionArg>,
+
+    /// Leave local branches pointing to the commits they pointed to before the
+    /// rebase
+    ///
+    /// WARNING: The original commits are hidden after the rebase, so the
+    /// branches will be left pointing to hidden commits. This is only useful
+    /// for tooling which moves the branches itself afterwards. Working-copy
+    /// commits are still updated.
+    #[arg(long)]
+    no_update_branches: bool,
+
+    /// Rewrite the description of each rebased revision with the given template
+    ///
+    /// The template is evaluated against the original commit, so for example
+    /// `'"PROJ-123: " ++ description'` adds a prefix to each description, and
+    /// `'description.remove_prefix("WIP: ")'` strips one. Revisions whose
+    /// description changes are rewritten even if they are already in place.
+    ///
+    /// Only works with `-r`.
+    #[arg(long, value_name = "TEMPLATE", requires = "revisions_or_targets_file")]
+    message_template: Option<String>,
+
+    /// Split each rebased revision at the given paths (can be repeated)
+    ///
+    /// After the rebase, each revision is split into a commit with the changes
+    /// to the given paths and a child commit with the remaining changes, like
+    /// `jj split` does. Descendants, branches and working copies are moved to
+    /// the second commit. A revision which has no changes on one side of the
+    /// split is left as a single commit.
+    ///
+    /// Only works with `-r`.
+    #[arg(
+        long,
+        value_name = "PATHS",
+        value_hint = clap::ValueHint::AnyPath,
+        requires = "revisions_or_targets_file"
+    )]
+    split_paths: Vec<String>,
+
+    /// Record each rebased commit as a separate operation
+    ///
+    /// This makes it possible to undo part of a large rebase with `jj op
+    /// restore`, at the cost of adding an operation per commit to the
+    /// operation log. The working copy is only updated by the last operation.
+    #[arg(
+        long,
+        conflicts_with_all = ["skip_emptied", "empty", "fold_trivial", "reorder", "split_paths", "no_update_branches"]
+    )]
+    atomic_steps: bool,
+
+    /// Don't ask for confirmation before rewriting many commits
+    ///
+    /// When run in a terminal, `jj rebase` asks for confirmation if it would
+    /// rewrite more commits than the `rebase.confirm-threshold` setting.
+    #[arg(long)]
+    yes: bool,
+}
+
+/// What to do with commits which are empty after the rebase
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum EmptyMode {
+    /// Keep all commits
+    Keep,
+    /// Abandon all commits which are empty, including ones which were already
+    /// empty before the rebase
+    Drop,
+    /// Abandon commits which became empty because of the rebase
+    DropNewlyEmpty,
+}
+
+/// How to rebase the revisions read from `--targets-file`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum TargetsMode {
+    /// Rebase the revisions as with `-r`
+    Revisions,
+    /// Rebase the revisions and their descendants as with `-s`
+    Source,
+    /// Rebase the branches containing the revisions as with `-b`
+    Branch,
+}
+
+/// Options handled by this command on top of the `RebaseOptions`.
+#[derive(Clone, Debug)]
+struct CliRebaseOptions {
+    /// Commits to arrange in a linear chain, listed from parent to child.
+    reorder: IndexSet<Commit>,
+    /// Fold rebased commits touching at most this many paths into their parent.
+    fold_trivial: Option<usize>,
+    /// Restore local branches to their targets before the rebase.
+    no_update_branches: bool,
+    /// Template for the new descriptions of the `-r` commits.
+    message_template: Option<String>,
+    /// Paths at which to split the `-r` commits after the rebase.
+    split_paths: Vec<String>,
+    /// Record each rebased commit as a separate operation.
+    atomic_steps: bool,
+    /// How to handle `-r` commits which become empty.
+    empty_targets: EmptyBehaviour,
+    /// How to handle descendants of the moved commits which become empty.
+    empty_descendants: EmptyBehaviour,
+    /// Keep the author timestamp of the rebased commits.
+    keep_author_timestamp: bool,
+    /// Print the new commit id of each rebased commit.
+    show_mapping: bool,
+    /// Show a graph of the rebased commits after the rebase.
+    show_result: bool,
+    /// Move the descendants of the `-r` commits along with them.
+    move_descendants: bool,
+    /// The target commits contain all of their own descendants, in the order
+    /// returned by evaluating `roots::`.
+    targets_include_descendants: bool,
+    /// Print the new parents of the moved commits instead of moving them.
+    explain: bool,
+    /// Ask for confirmation before rewriting more than this many commits.
+    confirm_threshold: Option<usize>,
 }
 
 #[instrument(skip_all)]
@@ -207,91 +513,143 @@ pub(crate) fn cmd_rebase(
     args: &RebaseArgs,
 ) -> Result<(), CommandError> {
     if args.skip_empty {
-        return Err(cli_error(
-            "--skip-empty is deprecated, and has been renamed to --skip-emptied.",
+        writeln!(
+            ui.warning_default(),
+            "--skip-empty is deprecated, use --empty=drop-newly-empty instead."
+        )?;
+    }
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let mut args = args.clone();
+    add_targets_from_file(command, &workspace_command, &mut args)?;
+    set_auto_destination(&workspace_command, &mut args)?;
+    let args = &args;
+    if args.explain
+        && args.revisions.is_empty()
+        && args.insert_after.is_empty()
+        && args.insert_before.is_empty()
+    {
+        return Err(user_error(
+            "--explain only works with -r, --insert-after, or --insert-before",
         ));
     }
 
+    let empty_mode = match args.empty {
+        Some(mode) => mode,
+        None if args.skip_emptied || args.skip_empty => EmptyMode::DropNewlyEmpty,
+        None => EmptyMode::Keep,
+    };
     let rebase_options = RebaseOptions {
-        empty: match args.skip_emptied {
-            true => EmptyBehaviour::AbandonNewlyEmpty,
-            false => EmptyBehaviour::Keep,
+        empty: match empty_mode {
+            EmptyMode::Keep => EmptyBehaviour::Keep,
+            EmptyMode::Drop => EmptyBehaviour::AbandonAllEmpty,
+            EmptyMode::DropNewlyEmpty => EmptyBehaviour::AbandonNewlyEmpty,
         },
         simplify_ancestor_merge: false,
+        keep_author_timestamp: args.keep_author_timestamp,
+    };
+    let mut cli_options = CliRebaseOptions {
+        reorder: IndexSet::new(),
+        fold_trivial: args.fold_trivial,
+        no_update_branches: args.no_update_branches,
+        message_template: args.message_template.clone(),
+        split_paths: args.split_paths.clone(),
+        atomic_steps: args.atomic_steps,
+        empty_targets: rebase_options.empty,
+        empty_descendants: EmptyBehaviour::Keep,
+        keep_author_timestamp: rebase_options.keep_author_timestamp,
+        show_mapping: args.show_mapping,
+        show_result: args.show_result,
+        move_descendants: args.move_descendants,
+        targets_include_descendants: false,
+        explain: args.explain,
+        confirm_threshold: if args.yes || !Ui::can_prompt() {
+            None
+        } else {
+            match command
+                .settings()
+                .config()
+                .get::<usize>("rebase.confirm-threshold")?
+            {
+                0 => None,
+                threshold => Some(threshold),
+            }
+        },
     };
-    let mut workspace_command = command.workspace_helper(ui)?;
     if !args.revisions.is_empty() {
-        assert_eq!(
-            // In principle, `-r --skip-empty` could mean to abandon the `-r`
-            // commit if it becomes empty. This seems internally consistent with
-            // the behavior of other commands, but is not very useful.
-            //
-            // It would become even more confusing once `-r --before` is
-            // implemented. If `rebase -r` behaves like `abandon`, the
-            // descendants of the `-r` commits should not be abandoned if
-            // emptied. But it would also make sense for the descendants of the
-            // `--before` commit to be abandoned if emptied. A commit can easily
-            // be in both categories.
-            rebase_options.empty,
-            EmptyBehaviour::Keep,
-            "clap should forbid `-r --skip-empty`"
-        );
         let target_commits: Vec<_> = workspace_command
             .parse_union_revsets(&args.revisions)?
             .evaluate_to_commits()?
             .try_collect()?; // in reverse topological order
-        if !args.insert_after.is_empty() && !args.insert_before.is_empty() {
-            let after_commits =
-                workspace_command.resolve_some_revsets_default_single(&args.insert_after)?;
-            let before_commits =
-                workspace_command.resolve_some_revsets_default_single(&args.insert_before)?;
-            rebase_revisions_after_before(
-                ui,
-                command.settings(),
-                &mut workspace_command,
-                &after_commits,
-                &before_commits,
-                &target_commits,
-            )?;
-        } else if !args.insert_after.is_empty() {
-            let after_commits =
-                workspace_command.resolve_some_revsets_default_single(&args.insert_after)?;
-            rebase_revisions_after(
-                ui,
-                command.settings(),
-                &mut workspace_command,
-                &after_commits,
-                &target_commits,
-            )?;
-        } else if !args.insert_before.is_empty() {
-            let before_commits =
-                workspace_command.resolve_some_revsets_default_single(&args.insert_before)?;
-            rebase_revisions_before(
+        if target_commits.is_empty() && !args.allow_empty_selection {
+            return Err(empty_selection_error(
+                "Source revset resolved to no commits",
+            ));
+        }
+        if args.interactive {
+            rebase_revisions_interactively(
                 ui,
                 command.settings(),
                 &mut workspace_command,
-                &before_commits,
                 &target_commits,
+                &cli_options,
             )?;
         } else {
-            let new_parents = workspace_command
-                .resolve_some_revsets_default_single(&args.destination)?
-                .into_iter()
-                .collect_vec();
-            rebase_revisions(
+            rebase_revisions_to_target(
                 ui,
-                command.settings(),
+                command,
                 &mut workspace_command,
-                &new_parents,
+                args,
                 &target_commits,
+                &cli_options,
             )?;
         }
+    } else if !args.insert_after.is_empty()
+        || !args.insert_before.is_empty()
+        || !args.skip.is_empty()
+    {
+        let target_commits = resolve_subtree_targets(&workspace_command, args)?;
+        if target_commits.is_empty() && !args.allow_empty_selection {
+            return Err(empty_selection_error(
+                "Branch resolved to no commits to rebase",
+            ));
+        }
+        // As with a plain `-s` or `-b` rebase, commits outside the selected
+        // subtree may also be abandoned.
+        cli_options.empty_descendants = rebase_options.empty;
+        // Without `--skip`, the subtree contains all descendants of its roots,
+        // so they don't need to be evaluated again.
+        cli_options.targets_include_descendants = args.skip.is_empty();
+        if !args.reorder.is_empty() {
+            cli_options.reorder =
+                workspace_command.resolve_some_revsets_default_single(&args.reorder)?;
+        }
+        check_reorder_commits(
+            workspace_command.repo(),
+            &target_commits.iter().cloned().collect(),
+            &cli_options.reorder,
+        )?;
+        rebase_revisions_to_target(
+            ui,
+            command,
+            &mut workspace_command,
+            args,
+            &target_commits,
+            &cli_options,
+        )?;
     } else if !args.source.is_empty() {
-        let new_parents = workspace_command
-            .resolve_some_revsets_default_single(&args.destination)?
+        let new_parents = resolve_destinations(ui, &workspace_command, &args.destination)?
             .into_iter()
             .collect_vec();
         let source_commits = workspace_command.resolve_some_revsets_default_single(&args.source)?;
+        if !args.reorder.is_empty() {
+            cli_options.reorder =
+                workspace_command.resolve_some_revsets_default_single(&args.reorder)?;
+        }
+        check_reorder_commits(
+            workspace_command.repo(),
+            &source_commits,
+            &cli_options.reorder,
+        )?;
         rebase_descendants_transaction(
             ui,
             command.settings(),
@@ -299,10 +657,10 @@ pub(crate) fn cmd_rebase(
             new_parents,
             &source_commits,
             rebase_options,
+            &cli_options,
         )?;
     } else {
-        let new_parents = workspace_command
-            .resolve_some_revsets_default_single(&args.destination)?
+        let new_parents = resolve_destinations(ui, &workspace_command, &args.destination)?
             .into_iter()
             .collect_vec();
         let branch_commits = if args.branch.is_empty() {
@@ -317,11 +675,115 @@ pub(crate) fn cmd_rebase(
             new_parents,
             &branch_commits,
             rebase_options,
+            &cli_options,
+            args.allow_empty_selection,
         )?;
     }
     Ok(())
 }
 
+/// Passes the revsets read from `--targets-file` to the argument selected by
+/// `--targets-mode`.
+fn add_targets_from_file(
+    command: &CommandHelper,
+    workspace_command: &WorkspaceCommandHelper,
+    args: &mut RebaseArgs,
+) -> Result<(), CommandError> {
+    let (Some(path), Some(mode)) = (&args.targets_file, args.targets_mode) else {
+        return Ok(());
+    };
+    let targets = read_targets_file(workspace_command, command.cwd(), path)?;
+    if targets.is_empty() {
+        return Err(user_error(format!("No revsets found in {path}")));
+    }
+    // Clap can only check that these flags are used with `--targets-file`, so
+    // check that they are used with the corresponding mode here.
+    let revisions_only_flags = [
+        ("--interactive", args.interactive),
+        ("--move-descendants", args.move_descendants),
+        ("--message-template", args.message_template.is_some()),
+        ("--split-paths", !args.split_paths.is_empty()),
+    ];
+    if mode != TargetsMode::Revisions {
+        if let Some((flag, _)) = revisions_only_flags.iter().find(|(_, used)| *used) {
+            return Err(cli_error(format!(
+                "{flag} only works with --targets-mode revisions"
+            )));
+        }
+    }
+    if mode != TargetsMode::Source && !args.reorder.is_empty() {
+        return Err(cli_error("--reorder only works with --targets-mode source"));
+    }
+    match mode {
+        TargetsMode::Revisions => {
+            if !args.skip.is_empty() {
+                return Err(cli_error(
+                    "--skip cannot be used with --targets-mode revisions",
+                ));
+            }
+            args.revisions = targets;
+        }
+        TargetsMode::Source => {
+            args.source = targets;
+        }
+        TargetsMode::Branch => {
+            args.branch = targets;
+        }
+    }
+    Ok(())
+}
+
+/// Sets the destination to `trunk()` if `--auto-destination` is given.
+fn set_auto_destination(
+    workspace_command: &WorkspaceCommandHelper,
+    args: &mut RebaseArgs,
+) -> Result<(), CommandError> {
+    if !args.auto_destination {
+        return Ok(());
+    }
+    let trunk_arg = RevisionArg::from("trunk()".to_owned());
+    let trunk_commit = workspace_command.resolve_single_rev(&trunk_arg)?;
+    // The default `trunk()` falls back to the root commit if there's no trunk
+    // branch.
+    if trunk_commit.id() == workspace_command.repo().store().root_commit_id() {
+        return Err(user_error_with_hint(
+            "The trunk() revision resolved to the root commit",
+            "Set `revset-aliases.\"trunk()\"` to your trunk branch, e.g. `main@origin`.",
+        ));
+    }
+    args.destination = vec![trunk_arg];
+    Ok(())
+}
+
+/// Reads revsets from the file at `path`, one per line. Blank lines and lines
+/// starting with `#` are ignored. Each revset is resolved so that an error can
+/// point to the line it came from.
+fn read_targets_file(
+    workspace_command: &WorkspaceCommandHelper,
+    cwd: &Path,
+    path: &str,
+) -> Result<Vec<RevisionArg>, CommandError> {
+    let content = std::fs::read_to_string(cwd.join(path))
+        .map_err(|err| user_error_with_message(format!("Failed to read {path}"), err))?;
+    let mut targets = vec![];
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let target = RevisionArg::from(line.to_owned());
+        let resolve = || -> Result<(), CommandError> {
+            workspace_command.parse_revset(&target)?.evaluate()?;
+            Ok(())
+        };
+        resolve()
+            .map_err(|err| err.hinted(format!("The revset is on line {} of {path}", index + 1)))?;
+        targets.push(target);
+    }
+    Ok(targets)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn rebase_branch(
     ui: &mut Ui,
     settings: &UserSettings,
@@ -329,6 +791,8 @@ fn rebase_branch(
     new_parents: Vec<Commit>,
     branch_commits: &IndexSet<Commit>,
     rebase_options: RebaseOptions,
+    cli_options: &CliRebaseOptions,
+    allow_empty_selection: bool,
 ) -> Result<(), CommandError> {
     let parent_ids = new_parents
         .iter()
@@ -347,6 +811,11 @@ fn rebase_branch(
         .iter()
         .commits(workspace_command.repo().store())
         .try_collect()?;
+    if root_commits.is_empty() && !allow_empty_selection {
+        return Err(empty_selection_error(
+            "Branch resolved to no commits to rebase",
+        ));
+    }
     rebase_descendants_transaction(
         ui,
         settings,
@@ -354,32 +823,74 @@ fn rebase_branch(
         new_parents,
         &root_commits,
         rebase_options,
+        cli_options,
+    )
+}
+
+fn empty_selection_error(message: &str) -> CommandError {
+    user_error_with_hint(
+        message,
+        "Pass `--allow-empty-selection` to rebase nothing without an error.",
     )
 }
 
-/// Rebases `old_commits` onto `new_parents`.
+/// Rebases `old_commits` onto `new_parents`. The `old_commits` are counted as
+/// targets in the returned stats.
 fn rebase_descendants(
     tx: &mut WorkspaceCommandTransaction,
     settings: &UserSettings,
     new_parents: Vec<Commit>,
     old_commits: &[impl Borrow<Commit>],
     rebase_options: RebaseOptions,
-) -> Result<usize, CommandError> {
+) -> Result<MoveCommitsStats, CommandError> {
+    let mut stats = MoveCommitsStats::default();
+    let mut rebased_commits = vec![];
     for old_commit in old_commits.iter() {
+        let old_commit = old_commit.borrow();
         let rewriter = CommitRewriter::new(
             tx.mut_repo(),
-            old_commit.borrow().clone(),
+            old_commit.clone(),
             new_parents
                 .iter()
                 .map(|parent| parent.id().clone())
                 .collect(),
         );
-        rebase_commit_with_options(settings, rewriter, &rebase_options)?;
+        match rebase_commit_with_options(settings, rewriter, &rebase_options)? {
+            RebasedCommit::Rewritten(new_commit) => {
+                stats.num_rebased_targets += 1;
+                rebased_commits.push((old_commit.clone(), new_commit));
+            }
+            RebasedCommit::Abandoned { .. } => {
+                stats.num_abandoned += 1;
+                stats.rebased_commits.insert(old_commit.id().clone(), None);
+            }
+        }
+    }
+    let rebase_map = tx
+        .mut_repo()
+        .rebase_descendants_with_options_return_map(settings, rebase_options)?;
+    let store = tx.repo().store().clone();
+    for (old_commit_id, new_commit_id) in &rebase_map {
+        let new_commit = store.get_commit(new_commit_id)?;
+        // Abandoned commits are mapped to their parent, which isn't a rewrite
+        // of them.
+        if new_commit.predecessor_ids().contains(old_commit_id) {
+            stats.num_rebased_descendants += 1;
+            rebased_commits.push((store.get_commit(old_commit_id)?, new_commit));
+        } else {
+            stats.num_abandoned += 1;
+            stats.rebased_commits.insert(old_commit_id.clone(), None);
+        }
+    }
+    for (old_commit, new_commit) in &rebased_commits {
+        if new_commit.has_conflict()? && !old_commit.has_conflict()? {
+            stats.num_newly_conflicted += 1;
+        }
+        stats
+            .rebased_commits
+            .insert(old_commit.id().clone(), Some(new_commit.id().clone()));
     }
-    let num_rebased = old_commits.len()
-        + tx.mut_repo()
-            .rebase_descendants_with_options(settings, rebase_options)?;
-    Ok(num_rebased)
+    Ok(stats)
 }
 
 fn rebase_descendants_transaction(
@@ -389,8 +900,15 @@ fn rebase_descendants_transaction(
     new_parents: Vec<Commit>,
     old_commits: &IndexSet<Commit>,
     rebase_options: RebaseOptions,
+    cli_options: &CliRebaseOptions,
 ) -> Result<(), CommandError> {
-    workspace_command.check_rewritable(old_commits.iter().ids())?;
+    let reorder_commits = &cli_options.reorder;
+    check_root_not_rebased(
+        workspace_command.repo().as_ref(),
+        &old_commits.iter().cloned().collect_vec(),
+        &new_parents,
+    )?;
+    check_all_rewritable(workspace_command, old_commits.iter().ids())?;
     let (skipped_commits, old_commits) = old_commits
         .iter()
         .partition::<Vec<_>, _>(|commit| commit.parent_ids().iter().eq(new_parents.iter().ids()));
@@ -401,17 +919,59 @@ fn rebase_descendants_transaction(
             "Skipped rebase of {num_skipped_rebases} commits that were already in place"
         )?;
     }
-    if old_commits.is_empty() {
+    if old_commits.is_empty() && reorder_commits.is_empty() {
+        writeln!(ui.status(), "Nothing changed.")?;
         return Ok(());
     }
-    for old_commit in old_commits.iter() {
-        check_rebase_destinations(workspace_command.repo(), &new_parents, old_commit)?;
+    check_rebase_destinations(workspace_command.repo(), &new_parents, &old_commits)?;
+    if let Some(threshold) = cli_options.confirm_threshold {
+        let num_to_rewrite = RevsetExpression::commits(
+            old_commits
+                .iter()
+                .map(|commit| commit.id().clone())
+                .collect(),
+        )
+        .descendants()
+        .evaluate_programmatic(workspace_command.repo().as_ref())?
+        .iter()
+        .count();
+        if num_to_rewrite > threshold {
+            confirm_rewrite(ui, num_to_rewrite)?;
+        }
     }
     let mut tx = workspace_command.start_transaction();
-    let num_rebased =
-        rebase_descendants(&mut tx, settings, new_parents, &old_commits, rebase_options)?;
-    writeln!(ui.status(), "Rebased {num_rebased} commits")?;
-    let tx_message = if old_commits.len() == 1 {
+    let mut rebased_commits = HashMap::new();
+    if !old_commits.is_empty() {
+        let stats =
+            rebase_descendants(&mut tx, settings, new_parents, &old_commits, rebase_options)?;
+        let num_rebased =
+            stats.num_rebased_targets + stats.num_rebased_descendants + stats.num_abandoned;
+        writeln!(ui.status(), "Rebased {num_rebased} commits")?;
+        if stats.num_newly_conflicted > 0 {
+            writeln!(
+                ui.status(),
+                "{} commits now have conflicts",
+                stats.num_newly_conflicted
+            )?;
+        }
+        warn_rewritten_remote_branches(ui, tx.repo(), &stats.rebased_commits)?;
+        if !cli_options.no_update_branches {
+            print_branches_moved_from_abandoned(
+                ui,
+                tx.base_repo().as_ref(),
+                tx.repo(),
+                &stats.rebased_commits,
+            )?;
+        }
+        if cli_options.show_mapping {
+            print_rebased_commits(ui, tx.repo(), &stats.rebased_commits)?;
+        }
+        rebased_commits = stats.rebased_commits;
+    }
+    post_process_rebase(ui, settings, &mut tx, cli_options)?;
+    let tx_message = if old_commits.is_empty() {
+        format!("reorder {} commits", reorder_commits.len())
+    } else if old_commits.len() == 1 {
         format!(
             "rebase commit {} and descendants",
             old_commits.first().unwrap().id().hex()
@@ -419,8 +979,16 @@ fn rebase_descendants_transaction(
     } else {
         format!("rebase {} commits and their descendants", old_commits.len())
     };
-    tx.finish(ui, tx_message)?;
-    Ok(())
+    if cli_options.atomic_steps {
+        let steps = collect_rebase_steps(settings, tx)?;
+        record_rebase_steps(ui, workspace_command, &steps)
+    } else {
+        tx.finish(ui, tx_message)?;
+        if cli_options.show_result {
+            print_rebase_result(ui, settings, workspace_command, &rebased_commits)?;
+        }
+        Ok(())
+    }
 }
 
 fn rebase_revisions(
@@ -429,12 +997,13 @@ fn rebase_revisions(
     workspace_command: &mut WorkspaceCommandHelper,
     new_parents: &[Commit],
     target_commits: &[Commit],
+    cli_options: &CliRebaseOptions,
 ) -> Result<(), CommandError> {
     if target_commits.is_empty() {
         return Ok(());
     }
 
-    workspace_command.check_rewritable(target_commits.iter().ids())?;
+    check_all_rewritable(workspace_command, target_commits.iter().ids())?;
     for commit in target_commits.iter() {
         if new_parents.contains(commit) {
             return Err(user_error(format!(
@@ -443,51 +1012,337 @@ fn rebase_revisions(
             )));
         }
     }
+    // Commands like `jj rebase -r @ -d @-` are common, so detect that nothing
+    // would change without starting a transaction. With `--explain`, the plan
+    // is printed even if nothing would change.
+    if cli_options.message_template.is_none()
+        && cli_options.split_paths.is_empty()
+        && !cli_options.explain
+        && revisions_already_in_place(
+            workspace_command.repo().as_ref(),
+            new_parents,
+            target_commits,
+        )?
+    {
+        writeln!(
+            ui.status(),
+            "Skipped rebase of {} commits that were already in place",
+            target_commits.len()
+        )?;
+        return Ok(());
+    }
 
     move_commits_transaction(
         ui,
         settings,
         workspace_command,
-        &new_parents.iter().ids().cloned().collect_vec(),
-        &[],
+        MoveDestination::Between(&new_parents.iter().ids().cloned().collect_vec(), &[]),
         target_commits,
+        cli_options,
     )
 }
 
-fn rebase_revisions_after(
-    ui: &mut Ui,
-    settings: &UserSettings,
-    workspace_command: &mut WorkspaceCommandHelper,
-    after_commits: &IndexSet<Commit>,
+/// Returns true if rebasing `target_commits` onto `new_parents` with `-r`
+/// would be a no-op, i.e. if each of them already has exactly `new_parents` as
+/// parents and none of them has children to rebase onto its parents.
+fn revisions_already_in_place(
+    repo: &dyn Repo,
+    new_parents: &[Commit],
     target_commits: &[Commit],
-) -> Result<(), CommandError> {
-    workspace_command.check_rewritable(target_commits.iter().ids())?;
+) -> Result<bool, CommandError> {
+    if !target_commits
+        .iter()
+        .all(|commit| commit.parent_ids().iter().eq(new_parents.iter().ids()))
+    {
+        return Ok(false);
+    }
+    // Since the targets are all siblings, any child is outside the target set.
+    let has_children = RevsetExpression::commits(target_commits.iter().ids().cloned().collect())
+        .children()
+        .evaluate_programmatic(repo)?
+        .iter()
+        .next()
+        .is_some();
+    Ok(!has_children)
+}
 
-    let after_commit_ids = after_commits.iter().ids().cloned().collect_vec();
-    let new_parents_expression = RevsetExpression::commits(after_commit_ids.clone());
-    let new_children_expression = new_parents_expression.children();
+/// Resolves each of `revision_args` like `resolve_some_revsets_default_single()`,
+/// but warns about and skips commits selected more than once instead of
+/// failing.
+fn resolve_unique_revsets(
+    ui: &Ui,
+    workspace_command: &WorkspaceCommandHelper,
+    revision_args: &[RevisionArg],
+) -> Result<IndexSet<Commit>, CommandError> {
+    let mut all_commits = IndexSet::new();
+    for revision_arg in revision_args {
+        let commits =
+            workspace_command.resolve_some_revsets_default_single(slice::from_ref(revision_arg))?;
+        for commit in commits {
+            let commit_hash = short_commit_hash(commit.id());
+            if !all_commits.insert(commit) {
+                writeln!(
+                    ui.warning_default(),
+                    "Ignoring repeated destination {commit_hash}"
+                )?;
+            }
+        }
+    }
+    Ok(all_commits)
+}
 
-    ensure_no_commit_loop(
-        workspace_command.repo().as_ref(),
-        &new_children_expression,
-        &new_parents_expression,
-    )?;
+/// Resolves the new parents given by `revision_args` with
+/// `resolve_unique_revsets()`, leaving out the ones which are ancestors of
+/// other new parents.
+fn resolve_destinations(
+    ui: &Ui,
+    workspace_command: &WorkspaceCommandHelper,
+    revision_args: &[RevisionArg],
+) -> Result<IndexSet<Commit>, CommandError> {
+    let mut commits = resolve_unique_revsets(ui, workspace_command, revision_args)?;
+    let parent_ids = commits.iter().ids().cloned().collect_vec();
+    let kept_ids: HashSet<_> =
+        remove_redundant_parents(ui, workspace_command.repo().as_ref(), &parent_ids)?
+            .into_iter()
+            .collect();
+    commits.retain(|commit| kept_ids.contains(commit.id()));
+    Ok(commits)
+}
 
-    let new_parent_ids = after_commit_ids;
-    let new_children: Vec<_> = new_children_expression
+/// Returns `parent_ids` without the commits which are ancestors of other
+/// commits in `parent_ids`, warning about each of them. Merging a commit with
+/// one of its descendants would produce a degenerate merge.
+fn remove_redundant_parents(
+    ui: &Ui,
+    repo: &dyn Repo,
+    parent_ids: &[CommitId],
+) -> Result<Vec<CommitId>, CommandError> {
+    let index = repo.index();
+    let is_redundant = |id: &CommitId| {
+        parent_ids
+            .iter()
+            .any(|other_id| other_id != id && index.is_ancestor(id, other_id))
+    };
+    let (kept_ids, redundant_ids): (Vec<_>, Vec<_>) =
+        parent_ids.iter().cloned().partition(|id| !is_redundant(id));
+    for id in &redundant_ids {
+        // Ancestry is acyclic, so one of the kept commits is a descendant.
+        let descendant_id = kept_ids
+            .iter()
+            .find(|kept_id| index.is_ancestor(id, kept_id))
+            .unwrap();
+        writeln!(
+            ui.warning_default(),
+            "Ignoring redundant destination {} (ancestor of {})",
+            short_commit_hash(id),
+            short_commit_hash(descendant_id)
+        )?;
+    }
+    Ok(kept_ids)
+}
+
+/// Resolves the commits moved by `-s` or `-b` together with `--insert-after`,
+/// `--insert-before`, or `--skip`, which are the roots selected by those
+/// arguments and all of their descendants, except for the skipped commits. The
+/// commits are returned in reverse topological order.
+fn resolve_subtree_targets(
+    workspace_command: &WorkspaceCommandHelper,
+    args: &RebaseArgs,
+) -> Result<Vec<Commit>, CommandError> {
+    // The destinations are only needed as a set here. They are resolved again
+    // with the usual checks and warnings when the commits are moved.
+    let evaluate_union = |revision_args: &[RevisionArg]| -> Result<Vec<_>, CommandError> {
+        if revision_args.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(workspace_command
+            .parse_union_revsets(revision_args)?
+            .evaluate_to_commit_ids()?
+            .collect())
+    };
+    let after_commit_ids = evaluate_union(&args.insert_after)?;
+    let before_commit_ids = evaluate_union(&args.insert_before)?;
+    // The commits to insert before are rewritten, so this also rejects the root
+    // commit, which has no parent to insert after.
+    check_all_rewritable(workspace_command, &before_commit_ids)?;
+    let roots_expression = if !args.source.is_empty() {
+        let source_commits = workspace_command.resolve_some_revsets_default_single(&args.source)?;
+        RevsetExpression::commits(source_commits.iter().ids().cloned().collect())
+    } else {
+        let branch_commits = if args.branch.is_empty() {
+            IndexSet::from([workspace_command.resolve_single_rev(&RevisionArg::AT)?])
+        } else {
+            workspace_command.resolve_some_revsets_default_single(&args.branch)?
+        };
+        // The branch is relative to the new parents, as with `-d`.
+        let new_parents_expression = if !after_commit_ids.is_empty() {
+            RevsetExpression::commits(after_commit_ids.clone())
+        } else if !before_commit_ids.is_empty() {
+            let store = workspace_command.repo().store();
+            let first_parent_ids: Vec<_> = before_commit_ids
+                .iter()
+                .map(|id| Ok(store.get_commit(id)?.parent_ids()[0].clone()))
+                .collect::<Result<_, CommandError>>()?;
+            RevsetExpression::commits(first_parent_ids)
+        } else {
+            RevsetExpression::commits(evaluate_union(&args.destination)?)
+        };
+        new_parents_expression
+            .range(&RevsetExpression::commits(
+                branch_commits.iter().ids().cloned().collect(),
+            ))
+            .roots()
+    };
+    let skipped_commit_ids: HashSet<_> = if args.skip.is_empty() {
+        HashSet::new()
+    } else {
+        workspace_command
+            .parse_union_revsets(&args.skip)?
+            .evaluate_to_commit_ids()?
+            .collect()
+    };
+    let target_commits: Vec<_> = roots_expression
+        .descendants()
         .evaluate_programmatic(workspace_command.repo().as_ref())?
         .iter()
+        .filter(|id| !skipped_commit_ids.contains(id))
         .commits(workspace_command.repo().store())
         .try_collect()?;
-    workspace_command.check_rewritable(new_children.iter().ids())?;
-
-    move_commits_transaction(
-        ui,
-        settings,
+    let target_commit_ids: HashSet<_> = target_commits.iter().ids().collect();
+    for commit_id in after_commit_ids.iter().chain(&before_commit_ids) {
+        if target_commit_ids.contains(commit_id) {
+            return Err(user_error_with_hint(
+                format!(
+                    "Cannot insert the rebased commits next to {}, since it is one of them",
+                    short_commit_hash(commit_id)
+                ),
+                "The destination is part of the commits being rebased. Use `jj rebase -r` to \
+                 rebase a revision without its descendants.",
+            ));
+        }
+    }
+    // As with a plain `-s` or `-b` rebase, the destinations must not be
+    // descendants of the moved commits, even if they are skipped.
+    if !args.destination.is_empty() {
+        let store = workspace_command.repo().store();
+        let destinations: Vec<_> = evaluate_union(&args.destination)?
+            .iter()
+            .map(|id| store.get_commit(id))
+            .try_collect()?;
+        let root_commits = target_commits
+            .iter()
+            .filter(|commit| {
+                !commit
+                    .parent_ids()
+                    .iter()
+                    .any(|id| target_commit_ids.contains(id))
+            })
+            .collect_vec();
+        check_rebase_destinations(workspace_command.repo(), &destinations, &root_commits)?;
+    }
+    Ok(target_commits)
+}
+
+/// Moves `target_commits` to the location given by `--destination`,
+/// `--insert-after`, and `--insert-before`.
+fn rebase_revisions_to_target(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    workspace_command: &mut WorkspaceCommandHelper,
+    args: &RebaseArgs,
+    target_commits: &[Commit],
+    cli_options: &CliRebaseOptions,
+) -> Result<(), CommandError> {
+    let after_commits = resolve_unique_revsets(ui, workspace_command, &args.insert_after)?;
+    let before_commits = resolve_unique_revsets(ui, workspace_command, &args.insert_before)?;
+    let new_parents = if after_commits.is_empty() && before_commits.is_empty() {
+        resolve_destinations(ui, workspace_command, &args.destination)?
+            .into_iter()
+            .collect_vec()
+    } else {
+        after_commits.iter().cloned().collect_vec()
+    };
+    check_root_not_rebased(
+        workspace_command.repo().as_ref(),
+        target_commits,
+        &new_parents,
+    )?;
+    if after_commits.is_empty() && before_commits.is_empty() {
+        rebase_revisions(
+            ui,
+            command.settings(),
+            workspace_command,
+            &new_parents,
+            target_commits,
+            cli_options,
+        )
+    } else if after_commits.is_empty() {
+        rebase_revisions_before(
+            ui,
+            command.settings(),
+            workspace_command,
+            &before_commits,
+            target_commits,
+            cli_options,
+        )
+    } else if before_commits.is_empty() {
+        rebase_revisions_after(
+            ui,
+            command.settings(),
+            workspace_command,
+            &after_commits,
+            target_commits,
+            cli_options,
+        )
+    } else {
+        rebase_revisions_after_before(
+            ui,
+            command.settings(),
+            workspace_command,
+            &after_commits,
+            &before_commits,
+            target_commits,
+            cli_options,
+        )
+    }
+}
+
+fn rebase_revisions_after(
+    ui: &mut Ui,
+    settings: &UserSettings,
+    workspace_command: &mut WorkspaceCommandHelper,
+    after_commits: &IndexSet<Commit>,
+    target_commits: &[Commit],
+    cli_options: &CliRebaseOptions,
+) -> Result<(), CommandError> {
+    let after_commit_ids = after_commits.iter().ids().cloned().collect_vec();
+    let new_parents_expression = RevsetExpression::commits(after_commit_ids.clone());
+    let new_children_expression = new_parents_expression.children();
+    let new_children: Vec<_> = new_children_expression
+        .evaluate_programmatic(workspace_command.repo().as_ref())?
+        .iter()
+        .commits(workspace_command.repo().store())
+        .try_collect()?;
+    check_all_rewritable(
         workspace_command,
-        &new_parent_ids,
-        &new_children,
+        target_commits.iter().chain(&new_children).ids(),
+    )?;
+
+    ensure_no_commit_loop(
+        workspace_command.repo().as_ref(),
+        &new_children_expression,
+        &new_parents_expression,
+    )?;
+
+    let new_parent_ids = after_commit_ids;
+
+    move_commits_transaction(
+        ui,
+        settings,
+        workspace_command,
+        MoveDestination::Between(&new_parent_ids, &new_children),
         target_commits,
+        cli_options,
     )
 }
 
@@ -497,13 +1352,25 @@ fn rebase_revisions_before(
     workspace_command: &mut WorkspaceCommandHelper,
     before_commits: &IndexSet<Commit>,
     target_commits: &[Commit],
+    cli_options: &CliRebaseOptions,
 ) -> Result<(), CommandError> {
-    workspace_command.check_rewritable(target_commits.iter().ids())?;
     let before_commit_ids = before_commits.iter().ids().cloned().collect_vec();
-    workspace_command.check_rewritable(&before_commit_ids)?;
+    check_all_rewritable(
+        workspace_command,
+        target_commits.iter().ids().chain(&before_commit_ids),
+    )?;
+
+    // Only the edge to the first parent of each commit is spliced, so a merge
+    // commit keeps its other parents and the rebased commits aren't inserted
+    // between them.
+    let new_parent_ids: IndexSet<_> = before_commits
+        .iter()
+        .map(|commit| commit.parent_ids()[0].clone())
+        .collect();
 
     let new_children_expression = RevsetExpression::commits(before_commit_ids);
-    let new_parents_expression = new_children_expression.parents();
+    let new_parents_expression =
+        RevsetExpression::commits(new_parent_ids.iter().cloned().collect());
 
     ensure_no_commit_loop(
         workspace_command.repo().as_ref(),
@@ -511,22 +1378,20 @@ fn rebase_revisions_before(
         &new_parents_expression,
     )?;
 
-    // Not using `new_parents_expression` here to persist the order of parents
-    // specified in `before_commits`.
-    let new_parent_ids: IndexSet<_> = before_commits
-        .iter()
-        .flat_map(|commit| commit.parent_ids().iter().cloned().collect_vec())
-        .collect();
-    let new_parent_ids = new_parent_ids.into_iter().collect_vec();
+    let new_parent_ids = remove_redundant_parents(
+        ui,
+        workspace_command.repo().as_ref(),
+        &new_parent_ids.into_iter().collect_vec(),
+    )?;
     let new_children = before_commits.iter().cloned().collect_vec();
 
     move_commits_transaction(
         ui,
         settings,
         workspace_command,
-        &new_parent_ids,
-        &new_children,
+        MoveDestination::Between(&new_parent_ids, &new_children),
         target_commits,
+        cli_options,
     )
 }
 
@@ -537,10 +1402,13 @@ fn rebase_revisions_after_before(
     after_commits: &IndexSet<Commit>,
     before_commits: &IndexSet<Commit>,
     target_commits: &[Commit],
+    cli_options: &CliRebaseOptions,
 ) -> Result<(), CommandError> {
-    workspace_command.check_rewritable(target_commits.iter().ids())?;
     let before_commit_ids = before_commits.iter().ids().cloned().collect_vec();
-    workspace_command.check_rewritable(&before_commit_ids)?;
+    check_all_rewritable(
+        workspace_command,
+        target_commits.iter().ids().chain(&before_commit_ids),
+    )?;
 
     let after_commit_ids = after_commits.iter().ids().cloned().collect_vec();
     let new_children_expression = RevsetExpression::commits(before_commit_ids);
@@ -552,32 +1420,226 @@ fn rebase_revisions_after_before(
         &new_parents_expression,
     )?;
 
-    let new_parent_ids = after_commit_ids;
+    let new_parent_ids =
+        remove_redundant_parents(ui, workspace_command.repo().as_ref(), &after_commit_ids)?;
     let new_children = before_commits.iter().cloned().collect_vec();
 
     move_commits_transaction(
         ui,
         settings,
         workspace_command,
-        &new_parent_ids,
-        &new_children,
+        MoveDestination::Between(&new_parent_ids, &new_children),
+        target_commits,
+        cli_options,
+    )
+}
+
+/// Asks the user whether to go on with a rebase rewriting `num_to_rewrite`
+/// commits. Fails if they don't answer yes.
+fn confirm_rewrite(ui: &Ui, num_to_rewrite: usize) -> Result<(), CommandError> {
+    let prompt = format!("This will rewrite {num_to_rewrite} commits. Continue?");
+    let confirmed = match ui.prompt_yes_no(&prompt, Some(false)) {
+        Ok(confirmed) => confirmed,
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => false,
+        Err(err) => return Err(err.into()),
+    };
+    if confirmed {
+        Ok(())
+    } else {
+        Err(user_error("Rebase aborted"))
+    }
+}
+
+/// Lets the user choose the new parents of each of `target_commits` in an
+/// editor, and moves each commit onto its new parents.
+fn rebase_revisions_interactively(
+    ui: &mut Ui,
+    settings: &UserSettings,
+    workspace_command: &mut WorkspaceCommandHelper,
+    target_commits: &[Commit],
+    cli_options: &CliRebaseOptions,
+) -> Result<(), CommandError> {
+    if target_commits.is_empty() {
+        return Ok(());
+    }
+    check_root_not_rebased(workspace_command.repo().as_ref(), target_commits, &[])?;
+    check_all_rewritable(workspace_command, target_commits.iter().ids())?;
+    let target_new_parent_ids = edit_target_parents(settings, workspace_command, target_commits)?;
+    ensure_no_parent_loop(target_commits, &target_new_parent_ids)?;
+    move_commits_transaction(
+        ui,
+        settings,
+        workspace_command,
+        MoveDestination::PerCommit(&target_new_parent_ids),
         target_commits,
+        cli_options,
     )
 }
 
-/// Wraps `move_commits` in a transaction.
+const INTERACTIVE_REBASE_INSTRUCTIONS: &str = r#"JJ: Edit the new parents of each revision below. Each line has the change id
+JJ: of a revision, a colon, and its parents separated by spaces. Parents can be
+JJ: any revsets which resolve to a single revision. Removing a line leaves the
+JJ: parents of that revision unchanged.
+JJ: Lines starting with "JJ: " (like this one) will be removed.
+"#;
+
+/// Opens an editor listing `target_commits` with their parents, and returns
+/// the new parents of each commit as edited by the user.
+fn edit_target_parents(
+    settings: &UserSettings,
+    workspace_command: &WorkspaceCommandHelper,
+    target_commits: &[Commit],
+) -> Result<HashMap<CommitId, Vec<CommitId>>, CommandError> {
+    let mut targets_by_change_hash: HashMap<String, &Commit> = HashMap::new();
+    for commit in target_commits {
+        let change_hash = short_change_hash(commit.change_id());
+        if targets_by_change_hash.insert(change_hash, commit).is_some() {
+            return Err(user_error(format!(
+                "Change {} is divergent and can't be rebased interactively",
+                short_change_hash(commit.change_id()),
+            )));
+        }
+    }
+
+    let mut content = INTERACTIVE_REBASE_INSTRUCTIONS.to_owned();
+    for commit in target_commits.iter().rev() {
+        content.push_str(&format!(
+            "\nJJ: {}\n{}: {}\n",
+            workspace_command.format_commit_summary(commit),
+            short_change_hash(commit.change_id()),
+            commit.parent_ids().iter().map(short_commit_hash).join(" "),
+        ));
+    }
+    let edited = edit_temp_file(
+        "rebase",
+        ".jjrebase",
+        workspace_command.repo().repo_path(),
+        &content,
+        settings,
+    )?;
+
+    let mut target_new_parent_ids: HashMap<_, _> = target_commits
+        .iter()
+        .map(|commit| (commit.id().clone(), commit.parent_ids().to_vec()))
+        .collect();
+    let mut edited_commit_ids = HashSet::new();
+    for line in edited.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("JJ: ") {
+            continue;
+        }
+        let Some((change_hash, parents)) = line.split_once(':') else {
+            return Err(user_error(format!("Invalid line in rebase file: {line}")));
+        };
+        let change_hash = change_hash.trim();
+        let Some(commit) = targets_by_change_hash.get(change_hash) else {
+            return Err(user_error(format!(
+                "Revision {change_hash} is not one of the revisions being rebased"
+            )));
+        };
+        if !edited_commit_ids.insert(commit.id().clone()) {
+            return Err(user_error(format!(
+                "Revision {change_hash} is listed more than once"
+            )));
+        }
+        let parent_ids: Vec<_> = parents
+            .split_whitespace()
+            .map(|parent| {
+                let parent_arg = RevisionArg::from(parent.to_owned());
+                Ok::<_, CommandError>(
+                    workspace_command
+                        .resolve_single_rev(&parent_arg)?
+                        .id()
+                        .clone(),
+                )
+            })
+            .process_results(|parent_ids| parent_ids.unique().collect())?;
+        if parent_ids.is_empty() {
+            return Err(user_error(format!(
+                "Revision {change_hash} must have at least one parent"
+            )));
+        }
+        target_new_parent_ids.insert(commit.id().clone(), parent_ids);
+    }
+    Ok(target_new_parent_ids)
+}
+
+/// Fails if the new parents of the target commits form a loop. Commits outside
+/// the target set never become descendants of target commits with `-r`, so
+/// only the parents within the target set need to be checked.
+fn ensure_no_parent_loop(
+    target_commits: &[Commit],
+    target_new_parent_ids: &HashMap<CommitId, Vec<CommitId>>,
+) -> Result<(), CommandError> {
+    let mut target_parents: HashMap<&CommitId, HashSet<&CommitId>> = target_new_parent_ids
+        .iter()
+        .map(|(commit_id, parent_ids)| {
+            let parent_ids = parent_ids
+                .iter()
+                .filter(|parent_id| target_new_parent_ids.contains_key(*parent_id))
+                .collect();
+            (commit_id, parent_ids)
+        })
+        .collect();
+    // Repeatedly drop the commits which have no parents or no children left.
+    // What remains is both an ancestor and a descendant of a loop.
+    loop {
+        let num_remaining = target_parents.len();
+        let has_children: HashSet<&CommitId> = target_parents.values().flatten().copied().collect();
+        target_parents.retain(|commit_id, parent_ids| {
+            !parent_ids.is_empty() && has_children.contains(commit_id)
+        });
+        let remaining: HashSet<&CommitId> = target_parents.keys().copied().collect();
+        for parent_ids in target_parents.values_mut() {
+            parent_ids.retain(|parent_id| remaining.contains(parent_id));
+        }
+        if target_parents.len() == num_remaining {
+            break;
+        }
+    }
+    let loop_commit_ids = target_commits
+        .iter()
+        .ids()
+        .filter(|commit_id| target_parents.contains_key(commit_id))
+        .cloned()
+        .collect_vec();
+    ensure_no_loop_commits(&loop_commit_ids)
+}
+
+/// Where [`move_commits_transaction()`] moves the target commits to.
+enum MoveDestination<'a> {
+    /// Insert the target commits between the given parents and children.
+    Between(&'a [CommitId], &'a [Commit]),
+    /// Move each target commit onto its own new parents.
+    PerCommit(&'a HashMap<CommitId, Vec<CommitId>>),
+}
+
+/// Moves `target_commits` with a `RebasePlan` in a transaction.
 fn move_commits_transaction(
     ui: &mut Ui,
     settings: &UserSettings,
     workspace_command: &mut WorkspaceCommandHelper,
-    new_parent_ids: &[CommitId],
-    new_children: &[Commit],
+    destination: MoveDestination,
     target_commits: &[Commit],
+    cli_options: &CliRebaseOptions,
 ) -> Result<(), CommandError> {
     if target_commits.is_empty() {
         return Ok(());
     }
 
+    let new_descriptions = match &cli_options.message_template {
+        Some(template_text) => {
+            render_descriptions(workspace_command, template_text, target_commits)?
+        }
+        None => HashMap::new(),
+    };
+    let split_matcher = if cli_options.split_paths.is_empty() {
+        None
+    } else {
+        let fileset_expression = workspace_command.parse_file_patterns(&cli_options.split_paths)?;
+        Some(fileset_expression.to_matcher())
+    };
+
     let mut tx = workspace_command.start_transaction();
     let tx_description = if target_commits.len() == 1 {
         format!("rebase commit {}", target_commits[0].id().hex())
@@ -589,17 +1651,57 @@ fn move_commits_transaction(
         )
     };
 
+    let mut plan = match destination {
+        MoveDestination::Between(new_parent_ids, new_children) => {
+            if cli_options.move_descendants {
+                check_descendants_can_follow(tx.repo(), target_commits, new_parent_ids)?;
+                RebasePlan::new_moving_descendants(
+                    tx.repo(),
+                    new_parent_ids,
+                    new_children,
+                    target_commits,
+                )?
+            } else if cli_options.targets_include_descendants {
+                RebasePlan::new_for_descendants(
+                    tx.repo(),
+                    new_parent_ids,
+                    new_children,
+                    target_commits,
+                )?
+            } else {
+                RebasePlan::new(tx.repo(), new_parent_ids, new_children, target_commits)?
+            }
+        }
+        MoveDestination::PerCommit(target_new_parent_ids) => {
+            RebasePlan::new_with_target_parents(tx.repo(), target_commits, target_new_parent_ids)?
+        }
+    };
+    plan.new_descriptions = new_descriptions;
+    plan.empty_targets = cli_options.empty_targets;
+    plan.empty_descendants = cli_options.empty_descendants;
+    plan.keep_author_timestamp = cli_options.keep_author_timestamp;
+    if cli_options.explain {
+        // The transaction is discarded without rewriting anything.
+        return print_rebase_plan(ui, &tx, &plan);
+    }
+    if let Some(threshold) = cli_options.confirm_threshold {
+        let num_to_rewrite = plan.to_visit_commits_new_parents.len();
+        if num_to_rewrite > threshold {
+            confirm_rewrite(ui, num_to_rewrite)?;
+        }
+    }
+    let progress = crate::progress::rebase_progress(ui);
     let MoveCommitsStats {
         num_rebased_targets,
         num_rebased_descendants,
         num_skipped_rebases,
-    } = move_commits(
-        settings,
-        tx.mut_repo(),
-        new_parent_ids,
-        new_children,
-        target_commits,
-    )?;
+        num_abandoned,
+        num_abandoned_descendants,
+        num_newly_conflicted,
+        rebased_commits,
+    } = plan.execute_with_progress(settings, tx.mut_repo(), progress.as_ref().map(|x| x as _))?;
+    // Clear the progress indicator before printing the summary.
+    drop(progress);
 
     if let Some(mut fmt) = ui.status_formatter() {
         if num_skipped_rebases > 0 {
@@ -617,354 +1719,604 @@ fn move_commits_transaction(
         if num_rebased_descendants > 0 {
             writeln!(fmt, "Rebased {num_rebased_descendants} descendant commits")?;
         }
+        if num_abandoned > 0 {
+            writeln!(fmt, "Abandoned {num_abandoned} newly emptied commits")?;
+        }
+        if num_abandoned_descendants > 0 {
+            writeln!(
+                fmt,
+                "Abandoned {num_abandoned_descendants} newly emptied descendant commits"
+            )?;
+        }
+        if num_newly_conflicted > 0 {
+            writeln!(fmt, "{num_newly_conflicted} commits now have conflicts")?;
+        }
+    }
+    warn_rewritten_remote_branches(ui, tx.repo(), &rebased_commits)?;
+    if !cli_options.no_update_branches {
+        print_branches_moved_from_abandoned(
+            ui,
+            tx.base_repo().as_ref(),
+            tx.repo(),
+            &rebased_commits,
+        )?;
+    }
+    let num_changed =
+        num_rebased_targets + num_rebased_descendants + num_abandoned + num_abandoned_descendants;
+    if num_changed == 0 && split_matcher.is_none() && cli_options.reorder.is_empty() {
+        // Discard the transaction so that no empty operation is recorded.
+        writeln!(ui.status(), "Nothing changed.")?;
+        return Ok(());
+    }
+    if cli_options.show_mapping {
+        print_rebased_commits(ui, tx.repo(), &rebased_commits)?;
+    }
+    if let Some(matcher) = &split_matcher {
+        // Abandoned commits have nothing left to split.
+        let split_commits = target_commits
+            .iter()
+            .filter(|commit| !matches!(rebased_commits.get(commit.id()), Some(None)))
+            .cloned()
+            .collect_vec();
+        let num_split =
+            split_commits_by_paths(&mut tx, settings, &split_commits, matcher.as_ref())?;
+        if num_split > 0 {
+            writeln!(ui.status(), "Split {num_split} commits at the given paths")?;
+        }
     }
+    post_process_rebase(ui, settings, &mut tx, cli_options)?;
 
-    tx.finish(ui, tx_description)
+    if cli_options.atomic_steps {
+        let steps = collect_rebase_steps(settings, tx)?;
+        record_rebase_steps(ui, workspace_command, &steps)
+    } else {
+        tx.finish(ui, tx_description)?;
+        if cli_options.show_result {
+            print_rebase_result(ui, settings, workspace_command, &rebased_commits)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the commits rebased in `tx` together with their original commits,
+/// with parents before children. The transaction is discarded.
+fn collect_rebase_steps(
+    settings: &UserSettings,
+    mut tx: WorkspaceCommandTransaction,
+) -> Result<Vec<(Commit, Commit)>, CommandError> {
+    tx.mut_repo().rebase_descendants(settings)?;
+    let old_heads =
+        RevsetExpression::commits(tx.base_repo().view().heads().iter().cloned().collect());
+    let new_heads = RevsetExpression::commits(tx.repo().view().heads().iter().cloned().collect());
+    let new_commits: Vec<_> = old_heads
+        .range(&new_heads)
+        .evaluate_programmatic(tx.repo())?
+        .iter()
+        .commits(tx.repo().store())
+        .try_collect()?; // in reverse topological order
+    let mut steps = vec![];
+    for new_commit in new_commits.into_iter().rev() {
+        let [old_commit_id] = new_commit.predecessor_ids() else {
+            return Err(internal_error(format!(
+                "Rebased commit {} doesn't have a single predecessor",
+                new_commit.id().hex()
+            )));
+        };
+        let old_commit = tx.repo().store().get_commit(old_commit_id)?;
+        steps.push((old_commit, new_commit));
+    }
+    Ok(steps)
 }
 
-struct MoveCommitsStats {
-    /// The number of commits in the target set which were rebased.
-    num_rebased_targets: u32,
-    /// The number of descendant commits which were rebased.
-    num_rebased_descendants: u32,
-    /// The number of commits for which rebase was skipped, due to the commit
-    /// already being in place.
-    num_skipped_rebases: u32,
+/// Records each of `steps` as a separate operation which replaces the original
+/// commit with the rebased one. Only the last operation updates the working
+/// copy.
+fn record_rebase_steps(
+    ui: &mut Ui,
+    workspace_command: &mut WorkspaceCommandHelper,
+    steps: &[(Commit, Commit)],
+) -> Result<(), CommandError> {
+    if steps.is_empty() {
+        writeln!(ui.status(), "Nothing changed.")?;
+        return Ok(());
+    }
+    for (index, (old_commit, new_commit)) in steps.iter().enumerate() {
+        let mut tx = workspace_command.start_transaction();
+        let mut_repo = tx.mut_repo();
+        mut_repo.add_head(new_commit)?;
+        // The original commit stays visible until its descendants have been
+        // rebased by later steps.
+        mut_repo.remove_head(old_commit.id());
+        let old_target = RefTarget::normal(old_commit.id().clone());
+        let new_target = RefTarget::normal(new_commit.id().clone());
+        let branch_names = mut_repo
+            .view()
+            .local_branches_for_commit(old_commit.id())
+            .map(|(name, _)| name.to_owned())
+            .collect_vec();
+        for name in branch_names {
+            mut_repo.merge_local_branch(&name, &old_target, &new_target);
+        }
+        let workspace_ids = mut_repo
+            .view()
+            .wc_commit_ids()
+            .iter()
+            .filter(|(_, commit_id)| *commit_id == old_commit.id())
+            .map(|(workspace_id, _)| workspace_id.clone())
+            .collect_vec();
+        for workspace_id in workspace_ids {
+            mut_repo.set_wc_commit(workspace_id, new_commit.id().clone())?;
+        }
+        let description = format!("rebase commit {}", old_commit.id().hex());
+        if index + 1 < steps.len() {
+            tx.finish_intermediate(description)?;
+        } else {
+            tx.finish(ui, description)?;
+        }
+    }
+    writeln!(ui.status(), "Recorded {} operations", steps.len())?;
+    Ok(())
 }
 
-/// Moves `target_commits` from their current location to a new location in the
-/// graph, given by the set of `new_parent_ids` and `new_children`.
-/// The roots of `target_commits` are rebased onto the new parents, while the
-/// new children are rebased onto the heads of `target_commits`.
-/// This assumes that `target_commits` and `new_children` can be rewritten, and
-/// there will be no cycles in the resulting graph.
-/// `target_commits` should be in reverse topological order.
-fn move_commits(
+/// Splits each of `commits` into a commit with the changes matching `matcher`
+/// and a child commit with the remaining changes. Commits which would leave
+/// one side of the split empty are not split. Returns the number of commits
+/// which were split.
+fn split_commits_by_paths(
+    tx: &mut WorkspaceCommandTransaction,
     settings: &UserSettings,
-    mut_repo: &mut MutableRepo,
-    new_parent_ids: &[CommitId],
-    new_children: &[Commit],
-    target_commits: &[Commit],
-) -> Result<MoveCommitsStats, CommandError> {
-    if target_commits.is_empty() {
-        return Ok(MoveCommitsStats {
-            num_rebased_targets: 0,
-            num_rebased_descendants: 0,
-            num_skipped_rebases: 0,
-        });
+    commits: &[Commit],
+    matcher: &dyn Matcher,
+) -> Result<usize, CommandError> {
+    let mut num_split = 0;
+    for commit in commits {
+        let commit = resolve_rewritten_commit(tx.repo(), commit)?;
+        let base_tree = commit.parent_tree(tx.repo())?;
+        let end_tree = commit.tree()?;
+        let selected_tree_id = restore_tree(&end_tree, &base_tree, matcher)?;
+        if selected_tree_id == base_tree.id() || &selected_tree_id == commit.tree_id() {
+            continue;
+        }
+        let first_commit = tx
+            .mut_repo()
+            .rewrite_commit(settings, &commit)
+            .set_tree_id(selected_tree_id)
+            .write()?;
+        let second_commit = tx
+            .mut_repo()
+            .rewrite_commit(settings, &commit)
+            .set_parents(vec![first_commit.id().clone()])
+            // Generate a new change id so that the commit being split doesn't
+            // become divergent.
+            .generate_new_change_id()
+            .write()?;
+        // Move descendants, branches, and working copies to the second part.
+        tx.mut_repo()
+            .set_rewritten_commit(commit.id().clone(), second_commit.id().clone());
+        tx.mut_repo().rebase_descendants(settings)?;
+        num_split += 1;
     }
+    Ok(num_split)
+}
 
-    let target_commit_ids: HashSet<_> = target_commits.iter().ids().cloned().collect();
+/// Renders the new description of each of `commits` with the given template.
+/// Commits whose description wouldn't change are omitted.
+fn render_descriptions(
+    workspace_command: &WorkspaceCommandHelper,
+    template_text: &str,
+    commits: &[Commit],
+) -> Result<HashMap<CommitId, String>, CommandError> {
+    let template = workspace_command.parse_commit_template(template_text)?;
+    let mut new_descriptions = HashMap::new();
+    for commit in commits {
+        let mut output = Vec::new();
+        template
+            .format(commit, &mut PlainTextFormatter::new(&mut output))
+            .expect("write() to PlainTextFormatter should never fail");
+        let description = String::from_utf8(output).expect("template output should be utf-8 bytes");
+        if description != commit.description() {
+            new_descriptions.insert(commit.id().clone(), description);
+        }
+    }
+    Ok(new_descriptions)
+}
 
-    let connected_target_commits: Vec<_> =
-        RevsetExpression::commits(target_commits.iter().ids().cloned().collect_vec())
-            .connected()
-            .evaluate_programmatic(mut_repo)?
-            .iter()
-            .commits(mut_repo.store())
-            .try_collect()?;
+/// Applies `options` to the commits rebased in `tx`.
+fn post_process_rebase(
+    ui: &mut Ui,
+    settings: &UserSettings,
+    tx: &mut WorkspaceCommandTransaction,
+    options: &CliRebaseOptions,
+) -> Result<(), CommandError> {
+    if !options.reorder.is_empty() {
+        let num_reordered = reorder_commits_in_tx(tx, settings, &options.reorder)?;
+        if num_reordered > 0 {
+            writeln!(ui.status(), "Reordered {num_reordered} commits")?;
+        }
+    }
+    if let Some(max_paths) = options.fold_trivial {
+        let num_folded = fold_trivial_commits(tx, settings, max_paths)?;
+        if num_folded > 0 {
+            writeln!(
+                ui.status(),
+                "Folded {num_folded} trivial commits into their parents"
+            )?;
+        }
+    }
+    if options.no_update_branches {
+        // Rebase any pending descendants first so that finishing the
+        // transaction doesn't move the branches again.
+        tx.mut_repo().rebase_descendants(settings)?;
+        let num_restored = restore_local_branches(tx);
+        if num_restored > 0 {
+            writeln!(
+                ui.warning_default(),
+                "Left {num_restored} branches pointing to their old commits, which are now \
+                 hidden"
+            )?;
+        }
+    }
+    Ok(())
+}
 
-    // Commits in the target set should only have other commits in the set as
-    // parents, except the roots of the set, which persist their original
-    // parents.
-    // If a commit in the set has a parent which is not in the set, but has
-    // an ancestor which is in the set, then the commit will have that ancestor
-    // as a parent.
-    let mut target_commits_internal_parents: HashMap<CommitId, Vec<CommitId>> = HashMap::new();
-    for commit in connected_target_commits.iter().rev() {
-        // The roots of the set will not have any parents found in `new_target_parents`,
-        // and will be stored in `new_target_parents` as an empty vector.
-        let mut new_parents = vec![];
-        for old_parent in commit.parent_ids() {
-            if target_commit_ids.contains(old_parent) {
-                new_parents.push(old_parent.clone());
-            } else if let Some(parents) = target_commits_internal_parents.get(old_parent) {
-                new_parents.extend(parents.iter().cloned());
-            }
+/// Points the local branches back to their targets at the start of the
+/// transaction. Returns the number of branches which were restored.
+fn restore_local_branches(tx: &mut WorkspaceCommandTransaction) -> usize {
+    let base_branches = tx
+        .base_repo()
+        .view()
+        .local_branches()
+        .map(|(name, target)| (name.to_owned(), target.clone()))
+        .collect_vec();
+    let mut num_restored = 0;
+    for (name, target) in base_branches {
+        if tx.repo().get_local_branch(&name) != target {
+            tx.mut_repo().set_local_branch_target(&name, target);
+            num_restored += 1;
         }
-        target_commits_internal_parents.insert(commit.id().clone(), new_parents);
     }
-    target_commits_internal_parents.retain(|id, _| target_commit_ids.contains(id));
+    num_restored
+}
 
-    // Compute the roots of `target_commits`.
-    let target_roots: HashSet<_> = target_commits_internal_parents
+/// Prints the new commit id of each rebased commit, parents first.
+fn print_rebased_commits(
+    ui: &Ui,
+    repo: &dyn Repo,
+    rebased_commits: &HashMap<CommitId, Option<CommitId>>,
+) -> Result<(), CommandError> {
+    let old_commit_ids = RevsetExpression::commits(rebased_commits.keys().cloned().collect())
+        .evaluate_programmatic(repo)?
         .iter()
-        .filter(|(_, parents)| parents.is_empty())
-        .map(|(commit_id, _)| commit_id.clone())
-        .collect();
+        .collect_vec();
+    for old_commit_id in old_commit_ids.iter().rev() {
+        match &rebased_commits[old_commit_id] {
+            Some(new_commit_id) => writeln!(
+                ui.stdout(),
+                "{} -> {}",
+                short_commit_hash(old_commit_id),
+                short_commit_hash(new_commit_id)
+            )?,
+            None => writeln!(
+                ui.stdout(),
+                "{} -> (abandoned)",
+                short_commit_hash(old_commit_id)
+            )?,
+        }
+    }
+    Ok(())
+}
 
-    // If a commit outside the target set has a commit in the target set as a
-    // parent, then - after the transformation - it should have that commit's
-    // ancestors which are not in the target set as parents.
-    let mut target_commits_external_parents: HashMap<CommitId, IndexSet<CommitId>> = HashMap::new();
-    for commit in target_commits.iter().rev() {
-        let mut new_parents = IndexSet::new();
-        for old_parent in commit.parent_ids() {
-            if let Some(parents) = target_commits_external_parents.get(old_parent) {
-                new_parents.extend(parents.iter().cloned());
-            } else {
-                new_parents.insert(old_parent.clone());
-            }
+/// Prints the old and new parents of each commit whose parents are changed by
+/// `plan`, parents before children.
+fn print_rebase_plan(
+    ui: &Ui,
+    tx: &WorkspaceCommandTransaction,
+    plan: &RebasePlan,
+) -> Result<(), CommandError> {
+    let store = tx.repo().store();
+    let mut formatter = ui.stdout_formatter();
+    for (commit, new_parent_ids) in plan.to_visit_commits_new_parents.values() {
+        if commit.parent_ids() == new_parent_ids {
+            continue;
+        }
+        write!(formatter, "Commit ")?;
+        tx.write_commit_summary(formatter.as_mut(), commit)?;
+        writeln!(formatter)?;
+        for parent in commit.parents() {
+            write!(formatter, "  Old parent: ")?;
+            tx.write_commit_summary(formatter.as_mut(), &parent?)?;
+            writeln!(formatter)?;
+        }
+        for new_parent_id in new_parent_ids {
+            write!(formatter, "  New parent: ")?;
+            tx.write_commit_summary(formatter.as_mut(), &store.get_commit(new_parent_id)?)?;
+            writeln!(formatter)?;
         }
-        target_commits_external_parents.insert(commit.id().clone(), new_parents);
     }
+    Ok(())
+}
 
-    // If the new parents include a commit in the target set, replace it with the
-    // commit's ancestors which are outside the set.
-    // e.g. `jj rebase -r A --before A`
-    let new_parent_ids: Vec<_> = new_parent_ids
-        .iter()
-        .flat_map(|parent_id| {
-            if let Some(parent_ids) = target_commits_external_parents.get(parent_id) {
-                parent_ids.iter().cloned().collect_vec()
-            } else {
-                [parent_id.clone()].to_vec()
-            }
-        })
-        .collect();
+/// Renders the rebased commits and their new parents as a graph, using the
+/// same templates as `jj log`.
+fn print_rebase_result(
+    ui: &Ui,
+    settings: &UserSettings,
+    workspace_command: &WorkspaceCommandHelper,
+    rebased_commits: &HashMap<CommitId, Option<CommitId>>,
+) -> Result<(), CommandError> {
+    let new_commit_ids = rebased_commits.values().flatten().cloned().collect_vec();
+    if new_commit_ids.is_empty() {
+        return Ok(());
+    }
+    let repo = workspace_command.repo();
+    let new_commits = RevsetExpression::commits(new_commit_ids);
+    let revset = new_commits
+        .union(&new_commits.parents())
+        .evaluate_programmatic(repo.as_ref())?;
+
+    let language = workspace_command.commit_template_language()?;
+    let template = workspace_command
+        .parse_template(
+            &language,
+            &settings.config().get_string("templates.log")?,
+            CommitTemplateLanguage::wrap_commit,
+        )?
+        .labeled("log");
+    let node_template = workspace_command
+        .parse_template(
+            &language,
+            &settings.commit_node_template(),
+            CommitTemplateLanguage::wrap_commit_opt,
+        )?
+        .labeled("node");
 
-    // If the new children include a commit in the target set, replace it with the
-    // commit's descendants which are outside the set.
-    // e.g. `jj rebase -r A --after A`
-    let new_children: Vec<_> = if new_children
+    let mut formatter = ui.stdout_formatter();
+    write_commit_graph(
+        ui,
+        settings,
+        formatter.as_mut(),
+        repo.store(),
+        TopoGroupedGraphIterator::new(revset.iter_graph()),
+        &template,
+        &node_template,
+        false,
+        |_, _| Ok(()),
+    )
+}
+
+/// Prints where the local branches pointing to the commits abandoned by the
+/// rebase were moved to.
+fn print_branches_moved_from_abandoned(
+    ui: &Ui,
+    old_repo: &dyn Repo,
+    new_repo: &dyn Repo,
+    rebased_commits: &HashMap<CommitId, Option<CommitId>>,
+) -> Result<(), CommandError> {
+    let moved_branches = rebased_commits
         .iter()
-        .any(|child| target_commit_ids.contains(child.id()))
-    {
-        let target_commits_descendants: Vec<_> =
-            RevsetExpression::commits(target_commit_ids.iter().cloned().collect_vec())
-                .union(
-                    &RevsetExpression::commits(target_commit_ids.iter().cloned().collect_vec())
-                        .children(),
-                )
-                .evaluate_programmatic(mut_repo)?
-                .iter()
-                .commits(mut_repo.store())
-                .try_collect()?;
-
-        // For all commits in the target set, compute its transitive descendant commits
-        // which are outside of the target set by up to 1 generation.
-        let mut target_commit_external_descendants: HashMap<CommitId, IndexSet<Commit>> =
-            HashMap::new();
-        // Iterate through all descendants of the target set, going through children
-        // before parents.
-        for commit in target_commits_descendants.iter() {
-            if !target_commit_external_descendants.contains_key(commit.id()) {
-                let children = if target_commit_ids.contains(commit.id()) {
-                    IndexSet::new()
-                } else {
-                    IndexSet::from([commit.clone()])
-                };
-                target_commit_external_descendants.insert(commit.id().clone(), children);
-            }
+        .filter(|(_, new_commit_id)| new_commit_id.is_none())
+        .flat_map(|(old_commit_id, _)| {
+            old_repo
+                .view()
+                .local_branches_for_commit(old_commit_id)
+                .map(move |(name, _)| (name, old_commit_id))
+        })
+        .sorted();
+    for (name, old_commit_id) in moved_branches {
+        let new_target = new_repo.view().get_local_branch(name);
+        if new_target.is_absent() || new_target.added_ids().contains(old_commit_id) {
+            continue;
+        }
+        writeln!(
+            ui.status(),
+            "Branch {name} moved from abandoned commit {} to {}",
+            short_commit_hash(old_commit_id),
+            new_target.added_ids().map(short_commit_hash).join(", ")
+        )?;
+    }
+    Ok(())
+}
 
-            let children = target_commit_external_descendants
-                .get(commit.id())
-                .unwrap()
-                .iter()
-                .cloned()
-                .collect_vec();
-            for parent_id in commit.parent_ids() {
-                if target_commit_ids.contains(parent_id) {
-                    if let Some(target_children) =
-                        target_commit_external_descendants.get_mut(parent_id)
-                    {
-                        target_children.extend(children.iter().cloned());
-                    } else {
-                        target_commit_external_descendants
-                            .insert(parent_id.clone(), children.iter().cloned().collect());
-                    }
-                };
-            }
+/// Warns about the remote branches pointing to commits rewritten by the
+/// rebase, since the local history no longer contains them.
+fn warn_rewritten_remote_branches(
+    ui: &Ui,
+    repo: &dyn Repo,
+    rebased_commits: &HashMap<CommitId, Option<CommitId>>,
+) -> Result<(), CommandError> {
+    for ((name, remote_name), remote_ref) in repo.view().all_remote_branches() {
+        if remote_name == git::REMOTE_NAME_FOR_LOCAL_GIT_REPO
+            || !remote_ref
+                .target
+                .added_ids()
+                .any(|id| rebased_commits.contains_key(id))
+        {
+            continue;
         }
+        let state = match remote_ref.state {
+            RemoteRefState::New => "untracked",
+            RemoteRefState::Tracking => "tracked",
+        };
+        writeln!(
+            ui.warning_default(),
+            "The {state} remote branch {name}@{remote_name} points to a rewritten commit"
+        )?;
+    }
+    Ok(())
+}
 
-        new_children
+/// Checks that `reorder_commits` can be reordered after rebasing
+/// `source_commits` and their descendants.
+fn check_reorder_commits(
+    repo: &Arc<ReadonlyRepo>,
+    source_commits: &IndexSet<Commit>,
+    reorder_commits: &IndexSet<Commit>,
+) -> Result<(), CommandError> {
+    for commit in reorder_commits {
+        if !source_commits
             .iter()
-            .flat_map(|child| {
-                if let Some(children) = target_commit_external_descendants.get(child.id()) {
-                    children.iter().cloned().collect_vec()
-                } else {
-                    [child.clone()].to_vec()
-                }
-            })
-            .collect()
-    } else {
-        new_children.to_vec()
-    };
+            .any(|source| repo.index().is_ancestor(source.id(), commit.id()))
+        {
+            return Err(user_error(format!(
+                "Cannot reorder {}, which is not part of the rebased commits",
+                short_commit_hash(commit.id()),
+            )));
+        }
+        if commit.parent_ids().len() > 1 {
+            return Err(user_error(format!(
+                "Cannot reorder merge commit {}",
+                short_commit_hash(commit.id()),
+            )));
+        }
+    }
+    Ok(())
+}
 
-    // Compute the parents of the new children, which will include the heads of the
-    // target set.
-    let new_children_parents: HashMap<_, _> = if !new_children.is_empty() {
-        // Compute the heads of the target set, which will be used as the parents of
-        // `new_children`.
-        let mut target_heads: HashSet<CommitId> = HashSet::new();
-        for commit in connected_target_commits.iter().rev() {
-            target_heads.insert(commit.id().clone());
-            for old_parent in commit.parent_ids() {
-                target_heads.remove(old_parent);
-            }
+/// Arranges `commits` in a linear chain in the given order, parents first.
+/// The commits are looked up by change id, so they may have been rewritten
+/// earlier in the transaction. Returns the number of commits which were moved.
+fn reorder_commits_in_tx(
+    tx: &mut WorkspaceCommandTransaction,
+    settings: &UserSettings,
+    commits: &IndexSet<Commit>,
+) -> Result<u32, CommandError> {
+    let mut num_reordered = 0;
+    for (parent, commit) in commits.iter().tuple_windows() {
+        let parent = resolve_rewritten_commit(tx.repo(), parent)?;
+        let commit = resolve_rewritten_commit(tx.repo(), commit)?;
+        if commit.parent_ids() == [parent.id().clone()] {
+            continue;
         }
-        let target_heads = connected_target_commits
-            .iter()
-            .rev()
-            .filter(|commit| {
-                target_heads.contains(commit.id()) && target_commit_ids.contains(commit.id())
-            })
-            .map(|commit| commit.id().clone())
-            .collect_vec();
+        let stats = move_commits(
+            settings,
+            tx.mut_repo(),
+            &[parent.id().clone()],
+            &[],
+            &[commit],
+        )?;
+        num_reordered += stats.num_rebased_targets;
+    }
+    Ok(num_reordered)
+}
 
-        new_children
-            .iter()
-            .map(|child_commit| {
-                let mut new_child_parent_ids: IndexSet<_> = child_commit
-                    .parent_ids()
-                    .iter()
-                    // Replace target commits with their parents outside the target set.
-                    .flat_map(|id| {
-                        if let Some(parents) = target_commits_external_parents.get(id) {
-                            parents.iter().cloned().collect_vec()
-                        } else {
-                            [id.clone()].to_vec()
-                        }
-                    })
-                    // Exclude any of the new parents of the target commits, since we are
-                    // "inserting" the target commits in between the new parents and the new
-                    // children.
-                    .filter(|id| {
-                        !new_parent_ids
-                            .iter()
-                            .any(|new_parent_id| new_parent_id == id)
-                    })
-                    .collect();
-
-                // Add `target_heads` as parents of the new child commit.
-                new_child_parent_ids.extend(target_heads.clone());
-
-                (
-                    child_commit.id().clone(),
-                    new_child_parent_ids.iter().cloned().collect_vec(),
-                )
-            })
-            .collect()
-    } else {
-        HashMap::new()
-    };
+/// Finds the visible commit with the same change id as `commit`.
+fn resolve_rewritten_commit(repo: &dyn Repo, commit: &Commit) -> Result<Commit, CommandError> {
+    match repo.resolve_change_id(commit.change_id()).as_deref() {
+        Some([commit_id]) => Ok(repo.store().get_commit(commit_id)?),
+        _ => Err(user_error(format!(
+            "Cannot find the rewritten commit for change {}",
+            short_change_hash(commit.change_id()),
+        ))),
+    }
+}
 
-    // Compute the set of commits to visit, which includes the target commits, the
-    // new children commits (if any), and their descendants.
-    let mut roots = target_roots.iter().cloned().collect_vec();
-    roots.extend(new_children.iter().ids().cloned());
-    let to_visit_expression = RevsetExpression::commits(roots).descendants();
-    let to_visit: Vec<_> = to_visit_expression
-        .evaluate_programmatic(mut_repo)?
+/// Folds the commits rebased in `tx` whose changes touch at most `max_paths`
+/// paths into their parent. Returns the number of folded commits.
+///
+/// A commit is only folded into a parent which was rebased in the same
+/// transaction, so commits outside the rebased set are never rewritten.
+fn fold_trivial_commits(
+    tx: &mut WorkspaceCommandTransaction,
+    settings: &UserSettings,
+    max_paths: usize,
+) -> Result<usize, CommandError> {
+    let old_heads =
+        RevsetExpression::commits(tx.base_repo().view().heads().iter().cloned().collect());
+    let new_heads = RevsetExpression::commits(tx.repo().view().heads().iter().cloned().collect());
+    let rebased_commits: Vec<_> = old_heads
+        .range(&new_heads)
+        .evaluate_programmatic(tx.repo())?
         .iter()
-        .commits(mut_repo.store())
-        .try_collect()?;
-    let to_visit_commits: IndexMap<_, _> = to_visit
-        .into_iter()
-        .map(|commit| (commit.id().clone(), commit))
-        .collect();
+        .commits(tx.repo().store())
+        .try_collect()?; // in reverse topological order
+    let rebased_commit_ids: HashSet<_> = rebased_commits.iter().ids().cloned().collect();
+    let wc_commit_ids: HashSet<_> = tx.repo().view().wc_commit_ids().values().cloned().collect();
 
-    let to_visit_commits_new_parents: HashMap<_, _> = to_visit_commits
-        .iter()
-        .map(|(commit_id, commit)| {
-            let new_parents =
-            // New child of the rebased target commits.
-            if let Some(new_child_parents) = new_children_parents.get(commit_id) {
-                new_child_parents.clone()
-            }
-            // Commits in the target set should persist only rebased parents from the target
-            // sets.
-            else if let Some(target_commit_parents) =
-                target_commits_internal_parents.get(commit_id)
-            {
-                // If the commit does not have any parents in the target set, it is one of the
-                // commits in the root set, and should be rebased onto the new destination.
-                if target_commit_parents.is_empty() {
-                    new_parent_ids.clone()
-                } else {
-                    target_commit_parents.clone()
-                }
-            }
-            // Commits outside the target set should have references to commits inside the set
-            // replaced.
-            else if commit
-                .parent_ids()
-                .iter()
-                .any(|id| target_commits_external_parents.contains_key(id))
-            {
-                let mut new_parents = vec![];
-                for parent in commit.parent_ids() {
-                    if let Some(parents) = target_commits_external_parents.get(parent) {
-                        new_parents.extend(parents.iter().cloned());
-                    } else {
-                        new_parents.push(parent.clone());
-                    }
-                }
-                new_parents
-            } else {
-                commit.parent_ids().iter().cloned().collect_vec()
-            };
+    let mut folded_ids = HashSet::new();
+    let mut fold_parent_ids = HashSet::new();
+    let mut folds = vec![];
+    // Visit parents before children, so that a commit which is folded can't
+    // receive another folded commit.
+    for commit in rebased_commits.iter().rev() {
+        let [parent_id] = commit.parent_ids() else {
+            continue;
+        };
+        if !rebased_commit_ids.contains(parent_id)
+            || wc_commit_ids.contains(commit.id())
+            || folded_ids.contains(parent_id)
+            || fold_parent_ids.contains(parent_id)
+        {
+            continue;
+        }
+        let parent = tx.repo().store().get_commit(parent_id)?;
+        let num_paths = parent
+            .tree()?
+            .diff(&commit.tree()?, &EverythingMatcher)
+            .take(max_paths + 1)
+            .count();
+        if num_paths > max_paths {
+            continue;
+        }
+        folded_ids.insert(commit.id().clone());
+        fold_parent_ids.insert(parent_id.clone());
+        folds.push((commit, parent));
+    }
 
-            (commit_id.clone(), new_parents)
-        })
-        .collect();
+    for (commit, parent) in &folds {
+        let description = combine_messages(tx.base_repo(), &[commit], parent, settings)?;
+        let new_parent = tx
+            .mut_repo()
+            .rewrite_commit(settings, parent)
+            .set_tree_id(commit.tree_id().clone())
+            .set_description(description)
+            .write()?;
+        tx.mut_repo()
+            .record_abandoned_commit_with_parents(commit.id().clone(), [new_parent.id().clone()]);
+    }
+    tx.mut_repo().rebase_descendants(settings)?;
+    Ok(folds.len())
+}
 
-    // Re-compute the order of commits to visit, such that each commit's new parents
-    // must be visited first.
-    let mut visited: HashSet<CommitId> = HashSet::new();
-    let mut to_visit = dag_walk::topo_order_reverse(
-        to_visit_commits.keys().cloned().collect_vec(),
-        |commit_id| commit_id.clone(),
-        |commit_id| -> Vec<CommitId> {
-            visited.insert(commit_id.clone());
-            to_visit_commits_new_parents
-                .get(commit_id)
-                .cloned()
-                .unwrap()
-                .iter()
-                // Only add parents which are in the set to be visited and have not already been
-                // visited.
-                .filter(|&id| to_visit_commits.contains_key(id) && !visited.contains(id))
-                .cloned()
-                .collect()
-        },
-    );
-
-    let mut num_rebased_targets = 0;
-    let mut num_rebased_descendants = 0;
-    let mut num_skipped_rebases = 0;
-
-    // Rebase each commit onto its new parents in the reverse topological order
-    // computed above.
-    // TODO(ilyagr): Consider making it possible for descendants of the target set
-    // to become emptied, like --skip-empty. This would require writing careful
-    // tests.
-    while let Some(old_commit_id) = to_visit.pop() {
-        let old_commit = to_visit_commits.get(&old_commit_id).unwrap();
-        let parent_ids = to_visit_commits_new_parents
-            .get(&old_commit_id)
-            .cloned()
-            .unwrap();
-        let new_parent_ids = mut_repo.new_parents(parent_ids);
-        let rewriter = CommitRewriter::new(mut_repo, old_commit.clone(), new_parent_ids);
-        if rewriter.parents_changed() {
-            rewriter.rebase(settings)?.write()?;
-            if target_commit_ids.contains(&old_commit_id) {
-                num_rebased_targets += 1;
-            } else {
-                num_rebased_descendants += 1;
-            }
-        } else {
-            num_skipped_rebases += 1;
-        }
+/// Checks that all of `commit_ids` can be rewritten. Unlike
+/// `WorkspaceCommandHelper::check_rewritable()`, all the commits which can't be
+/// rewritten are reported at once.
+fn check_all_rewritable<'a>(
+    workspace_command: &WorkspaceCommandHelper,
+    commit_ids: impl IntoIterator<Item = &'a CommitId>,
+) -> Result<(), CommandError> {
+    let immutable_ids = workspace_command.find_immutable_commits(commit_ids)?;
+    match &immutable_ids[..] {
+        [] => Ok(()),
+        [commit_id] => workspace_command.check_rewritable([commit_id]),
+        _ => Err(user_error_with_hint(
+            format!(
+                "Commits {} are immutable",
+                format_commit_list(&immutable_ids)
+            ),
+            "Pass `--ignore-immutable` or configure the set of immutable commits via \
+             `revset-aliases.immutable_heads()`.",
+        )),
     }
-    mut_repo.update_rewritten_references(settings)?;
+}
 
-    Ok(MoveCommitsStats {
-        num_rebased_targets,
-        num_rebased_descendants,
-        num_skipped_rebases,
-    })
+/// The maximum number of commits listed in an error message.
+const MAX_LISTED_COMMITS: usize = 10;
+
+/// Formats `commit_ids` as a comma-separated list of short hashes. At most
+/// `MAX_LISTED_COMMITS` are listed, followed by the number of omitted ones.
+fn format_commit_list(commit_ids: &[CommitId]) -> String {
+    let mut text = commit_ids
+        .iter()
+        .take(MAX_LISTED_COMMITS)
+        .map(short_commit_hash)
+        .join(", ");
+    if commit_ids.len() > MAX_LISTED_COMMITS {
+        text.push_str(&format!(
+            " and {} more",
+            commit_ids.len() - MAX_LISTED_COMMITS
+        ));
+    }
+    text
 }
 
 /// Ensure that there is no possible cycle between the potential children and
@@ -974,34 +2326,157 @@ fn ensure_no_commit_loop(
     children_expression: &Rc<RevsetExpression>,
     parents_expression: &Rc<RevsetExpression>,
 ) -> Result<(), CommandError> {
-    if let Some(commit_id) = children_expression
+    let commit_ids = children_expression
         .dag_range_to(parents_expression)
         .evaluate_programmatic(repo)?
         .iter()
-        .next()
-    {
-        return Err(user_error(format!(
+        .collect_vec();
+    ensure_no_loop_commits(&commit_ids)
+}
+
+/// Fails if any commits would be both ancestors and descendants of the
+/// rebased commits.
+fn ensure_no_loop_commits(commit_ids: &[CommitId]) -> Result<(), CommandError> {
+    match commit_ids {
+        [] => Ok(()),
+        [commit_id] => Err(user_error(format!(
             "Refusing to create a loop: commit {} would be both an ancestor and a descendant of \
              the rebased commits",
-            short_commit_hash(&commit_id),
-        )));
+            short_commit_hash(commit_id),
+        ))),
+        _ => Err(user_error(format!(
+            "Refusing to create a loop: commits {} would be both ancestors and descendants of the \
+             rebased commits",
+            format_commit_list(commit_ids),
+        ))),
+    }
+}
+
+/// Fails if one of `new_parent_ids` is a descendant of `target_commits` which
+/// would be moved along with them by `--move-descendants`.
+fn check_descendants_can_follow(
+    repo: &dyn Repo,
+    target_commits: &[Commit],
+    new_parent_ids: &[CommitId],
+) -> Result<(), CommandError> {
+    let target_commit_ids: HashSet<_> = target_commits.iter().ids().collect();
+    for commit in target_commits {
+        let descendant_ids = new_parent_ids
+            .iter()
+            .filter(|id| {
+                !target_commit_ids.contains(id) && repo.index().is_ancestor(commit.id(), id)
+            })
+            .cloned()
+            .collect_vec();
+        let destination = match &descendant_ids[..] {
+            [] => continue,
+            [descendant_id] => format!("descendant {}", short_commit_hash(descendant_id)),
+            _ => format!("descendants {}", format_commit_list(&descendant_ids)),
+        };
+        return Err(user_error_with_hint(
+            format!(
+                "Cannot rebase {} onto its {destination} while moving its descendants",
+                short_commit_hash(commit.id()),
+            ),
+            "Rebase without `--move-descendants` to leave the descendants in place.",
+        ));
     }
     Ok(())
 }
 
+/// Fails if the root commit is among `target_commits`. If it's also among
+/// `new_parents`, this is reported like rebasing any other commit onto itself.
+fn check_root_not_rebased(
+    repo: &dyn Repo,
+    target_commits: &[Commit],
+    new_parents: &[Commit],
+) -> Result<(), CommandError> {
+    let root_commit_id = repo.store().root_commit_id();
+    if !target_commits.iter().ids().contains(root_commit_id) {
+        return Ok(());
+    }
+    if new_parents.iter().ids().contains(root_commit_id) {
+        Err(user_error(format!(
+            "Cannot rebase {} onto itself",
+            short_commit_hash(root_commit_id)
+        )))
+    } else {
+        Err(user_error("Cannot rebase the root commit"))
+    }
+}
+
+/// Fails if any of `commits` would be rebased onto itself or onto one of its
+/// descendants. All such commits are reported in one error.
 fn check_rebase_destinations(
     repo: &Arc<ReadonlyRepo>,
     new_parents: &[Commit],
-    commit: &Commit,
+    commits: &[impl Borrow<Commit>],
 ) -> Result<(), CommandError> {
-    for parent in new_parents {
-        if repo.index().is_ancestor(commit.id(), parent.id()) {
-            return Err(user_error(format!(
-                "Cannot rebase {} onto descendant {}",
-                short_commit_hash(commit.id()),
-                short_commit_hash(parent.id())
-            )));
+    let invalid_rebases = commits
+        .iter()
+        .filter_map(|commit| {
+            let commit = commit.borrow();
+            let destinations = invalid_rebase_destinations(repo, new_parents, commit)?;
+            Some(format!(
+                "{} onto {destinations}",
+                short_commit_hash(commit.id())
+            ))
+        })
+        .collect_vec();
+    let message = match &invalid_rebases[..] {
+        [] => return Ok(()),
+        [invalid_rebase] => format!("Cannot rebase {invalid_rebase}"),
+        _ => format!(
+            "Cannot rebase {} commits onto their destinations:\n{}",
+            invalid_rebases.len(),
+            invalid_rebases
+                .iter()
+                .map(|invalid_rebase| format!("  {invalid_rebase}"))
+                .join("\n")
+        ),
+    };
+    Err(user_error_with_hint(
+        message,
+        "The destination is part of the commits being rebased. Use `jj rebase -r` to rebase a \
+         revision without its descendants.",
+    ))
+}
+
+/// Describes the destinations among `new_parents` which would be rebased
+/// along with `commit`, or returns `None` if there are none.
+fn invalid_rebase_destinations(
+    repo: &Arc<ReadonlyRepo>,
+    new_parents: &[Commit],
+    commit: &Commit,
+) -> Option<String> {
+    // The destination would be rebased along with the commit, so there is no
+    // sensible place to put it.
+    let onto_itself = new_parents.iter().any(|parent| parent.id() == commit.id());
+    let descendant_ids = new_parents
+        .iter()
+        .map(|parent| parent.id())
+        .filter(|id| *id != commit.id() && repo.index().is_ancestor(commit.id(), id))
+        .cloned()
+        .collect_vec();
+    let mut destinations = vec![];
+    if onto_itself {
+        destinations.push("itself".to_owned());
+    }
+    match &descendant_ids[..] {
+        [] => {}
+        [descendant_id] => {
+            destinations.push(format!("descendant {}", short_commit_hash(descendant_id)));
+        }
+        _ => {
+            destinations.push(format!(
+                "descendants {}",
+                format_commit_list(&descendant_ids)
+            ));
         }
     }
-    Ok(())
+    if destinations.is_empty() {
+        None
+    } else {
+        Some(destinations.join(" or "))
+    }
 }
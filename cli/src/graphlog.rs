@@ -17,9 +17,19 @@ use std::io;
 use std::io::Write;
 
 use itertools::Itertools;
+use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
+use jj_lib::graph::{GraphEdge, GraphEdgeType};
 use jj_lib::settings::UserSettings;
+use jj_lib::store::Store;
 use renderdag::{Ancestor, GraphRowRenderer, Renderer};
 
+use crate::cli_util::{format_template, LogContentFormat};
+use crate::command_error::CommandError;
+use crate::formatter::Formatter;
+use crate::templater::TemplateRenderer;
+use crate::ui::Ui;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 // An edge to another node in the graph
 pub enum Edge<T> {
@@ -115,3 +125,94 @@ pub fn get_graphlog<'a, K: Clone + Eq + Hash + 'a>(
         _ => SaplingGraphLog::create(builder.build_box_drawing(), formatter),
     }
 }
+
+/// Renders the commits from `graph_iter` as a graph with `template`, like
+/// `jj log` does.
+///
+/// If `use_elided_nodes` is true, indirect edges go through a synthetic
+/// "(elided revisions)" node. `write_extra_content` is called after the
+/// template output of each commit, e.g. to show its diff.
+#[allow(clippy::too_many_arguments)]
+pub fn write_commit_graph(
+    ui: &Ui,
+    settings: &UserSettings,
+    formatter: &mut dyn Formatter,
+    store: &Store,
+    graph_iter: impl IntoIterator<Item = (CommitId, Vec<GraphEdge<CommitId>>)>,
+    template: &TemplateRenderer<Commit>,
+    node_template: &TemplateRenderer<Option<Commit>>,
+    use_elided_nodes: bool,
+    mut write_extra_content: impl FnMut(&mut dyn Formatter, &Commit) -> Result<(), CommandError>,
+) -> Result<(), CommandError> {
+    let with_content_format = LogContentFormat::new(ui, settings)?;
+    let mut graph = get_graphlog(settings, formatter.raw());
+    for (commit_id, edges) in graph_iter {
+        // The graph is keyed by (CommitId, is_synthetic)
+        let mut graphlog_edges = vec![];
+        // TODO: Should we update revset.iter_graph() to yield this flag instead of all
+        // the missing edges since we don't care about where they point here
+        // anyway?
+        let mut has_missing = false;
+        let mut elided_targets = vec![];
+        for edge in edges {
+            match edge.edge_type {
+                GraphEdgeType::Missing => {
+                    has_missing = true;
+                }
+                GraphEdgeType::Direct => {
+                    graphlog_edges.push(Edge::Direct((edge.target, false)));
+                }
+                GraphEdgeType::Indirect => {
+                    if use_elided_nodes {
+                        elided_targets.push(edge.target.clone());
+                        graphlog_edges.push(Edge::Direct((edge.target, true)));
+                    } else {
+                        graphlog_edges.push(Edge::Indirect((edge.target, false)));
+                    }
+                }
+            }
+        }
+        if has_missing {
+            graphlog_edges.push(Edge::Missing);
+        }
+        let mut buffer = vec![];
+        let key = (commit_id, false);
+        let commit = store.get_commit(&key.0)?;
+        with_content_format.write_graph_text(
+            ui.new_formatter(&mut buffer).as_mut(),
+            |formatter| template.format(&commit, formatter),
+            || graph.width(&key, &graphlog_edges),
+        )?;
+        if !buffer.ends_with(b"\n") {
+            buffer.push(b'\n');
+        }
+        write_extra_content(ui.new_formatter(&mut buffer).as_mut(), &commit)?;
+
+        let node_symbol = format_template(ui, &Some(commit), node_template);
+        graph.add_node(
+            &key,
+            &graphlog_edges,
+            &node_symbol,
+            &String::from_utf8_lossy(&buffer),
+        )?;
+        for elided_target in elided_targets {
+            let elided_key = (elided_target, true);
+            let real_key = (elided_key.0.clone(), false);
+            let edges = [Edge::Direct(real_key)];
+            let mut buffer = vec![];
+            with_content_format.write_graph_text(
+                ui.new_formatter(&mut buffer).as_mut(),
+                |formatter| writeln!(formatter.labeled("elided"), "(elided revisions)"),
+                || graph.width(&elided_key, &edges),
+            )?;
+            let node_symbol = format_template(ui, &None, node_template);
+            graph.add_node(
+                &elided_key,
+                &edges,
+                &node_symbol,
+                &String::from_utf8_lossy(&buffer),
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -209,6 +209,48 @@ pub fn snapshot_progress(ui: &Ui) -> Option<impl Fn(&RepoPath) + '_> {
     })
 }
 
+pub fn rebase_progress(ui: &Ui) -> Option<impl Fn(usize, usize)> {
+    struct State {
+        guard: Option<OutputGuard>,
+        output: ProgressOutput,
+        next_display_time: Instant,
+    }
+
+    let output = ui.progress_output()?;
+
+    // Don't clutter the output during fast operations.
+    let next_display_time = Instant::now() + INITIAL_DELAY;
+    let state = Mutex::new(State {
+        guard: None,
+        output,
+        next_display_time,
+    });
+
+    Some(move |current: usize, total: usize| {
+        let mut state = state.lock().unwrap();
+        let now = Instant::now();
+        if now < state.next_display_time {
+            return;
+        }
+        state.next_display_time = now + Duration::from_secs(1) / UPDATE_HZ;
+
+        if state.guard.is_none() {
+            state.guard = Some(
+                state
+                    .output
+                    .output_guard(format!("\r{}", Clear(ClearType::CurrentLine))),
+            );
+        }
+
+        _ = write!(
+            state.output,
+            "\r{}Rebasing commit {current} of {total}",
+            Clear(ClearType::CurrentLine),
+        );
+        _ = state.output.flush();
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -279,6 +279,15 @@ fn test_rewrite_immutable_commands() {
     Error: Commit 1d5af877b8bb is immutable
     Hint: Pass `--ignore-immutable` or configure the set of immutable commits via `revset-aliases.immutable_heads()`.
     "###);
+    // rebase -r with several immutable commits
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["rebase", "-r=main", "-r=description(c)", "-d=@"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Commits 1d5af877b8bb, 77cee210cbf5 are immutable
+    Hint: Pass `--ignore-immutable` or configure the set of immutable commits via `revset-aliases.immutable_heads()`.
+    "###);
     // resolve
     let stderr = test_env.jj_cmd_failure(&repo_path, &["resolve", "-r=description(merge)", "file"]);
     insta::assert_snapshot!(stderr, @r###"
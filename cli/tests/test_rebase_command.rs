@@ -14,7 +14,7 @@
 
 use std::path::Path;
 
-use crate::common::TestEnvironment;
+use crate::common::{get_stderr_string, get_stdout_string, TestEnvironment};
 
 fn create_commit(test_env: &TestEnvironment, repo_path: &Path, name: &str, parents: &[&str]) {
     if parents.is_empty() {
@@ -41,9 +41,9 @@ fn test_rebase_invalid() {
     let stderr = test_env.jj_cmd_cli_error(&repo_path, &["rebase"]);
     insta::assert_snapshot!(stderr, @r###"
     error: the following required arguments were not provided:
-      <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>>
+      <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>|--auto-destination|--interactive>
 
-    Usage: jj rebase <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>>
+    Usage: jj rebase <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>|--auto-destination|--interactive>
 
     For more information, try '--help'.
     "###);
@@ -54,7 +54,7 @@ fn test_rebase_invalid() {
     insta::assert_snapshot!(stderr, @r###"
     error: the argument '--revisions <REVISIONS>' cannot be used with '--source <SOURCE>'
 
-    Usage: jj rebase --revisions <REVISIONS> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>>
+    Usage: jj rebase --revisions <REVISIONS> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>|--auto-destination|--interactive>
 
     For more information, try '--help'.
     "###);
@@ -65,20 +65,7 @@ fn test_rebase_invalid() {
     insta::assert_snapshot!(stderr, @r###"
     error: the argument '--branch <BRANCH>' cannot be used with '--source <SOURCE>'
 
-    Usage: jj rebase --branch <BRANCH> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>>
-
-    For more information, try '--help'.
-    "###);
-
-    // Both -r and --skip-empty
-    let stderr = test_env.jj_cmd_cli_error(
-        &repo_path,
-        &["rebase", "-r", "a", "-d", "b", "--skip-empty"],
-    );
-    insta::assert_snapshot!(stderr, @r###"
-    error: the argument '--revisions <REVISIONS>' cannot be used with '--skip-empty'
-
-    Usage: jj rebase --revisions <REVISIONS> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>>
+    Usage: jj rebase --branch <BRANCH> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>|--auto-destination|--interactive>
 
     For more information, try '--help'.
     "###);
@@ -91,29 +78,23 @@ fn test_rebase_invalid() {
     insta::assert_snapshot!(stderr, @r###"
     error: the argument '--destination <DESTINATION>' cannot be used with '--insert-after <INSERT_AFTER>'
 
-    Usage: jj rebase --revisions <REVISIONS> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>>
+    Usage: jj rebase --revisions <REVISIONS> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>|--auto-destination|--interactive>
 
     For more information, try '--help'.
     "###);
 
-    // -s with --after
-    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["rebase", "-s", "a", "--after", "b"]);
+    // -s with --after a descendant
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-s", "a", "--after", "b"]);
     insta::assert_snapshot!(stderr, @r###"
-    error: the argument '--source <SOURCE>' cannot be used with '--insert-after <INSERT_AFTER>'
-
-    Usage: jj rebase --source <SOURCE> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>>
-
-    For more information, try '--help'.
+    Error: Cannot insert the rebased commits next to 1394f625cbbd, since it is one of them
+    Hint: The destination is part of the commits being rebased. Use `jj rebase -r` to rebase a revision without its descendants.
     "###);
 
-    // -b with --after
-    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["rebase", "-b", "a", "--after", "b"]);
+    // -b with --after a descendant
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-b", "a", "--after", "b"]);
     insta::assert_snapshot!(stderr, @r###"
-    error: the argument '--branch <BRANCH>' cannot be used with '--insert-after <INSERT_AFTER>'
-
-    Usage: jj rebase --branch <BRANCH> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>>
-
-    For more information, try '--help'.
+    Error: Branch resolved to no commits to rebase
+    Hint: Pass `--allow-empty-selection` to rebase nothing without an error.
     "###);
 
     // Both -d and --before
@@ -124,29 +105,35 @@ fn test_rebase_invalid() {
     insta::assert_snapshot!(stderr, @r###"
     error: the argument '--destination <DESTINATION>' cannot be used with '--insert-before <INSERT_BEFORE>'
 
-    Usage: jj rebase --revisions <REVISIONS> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>>
+    Usage: jj rebase --revisions <REVISIONS> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>|--auto-destination|--interactive>
 
     For more information, try '--help'.
     "###);
 
-    // -s with --before
-    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["rebase", "-s", "a", "--before", "b"]);
+    // -s with --before a descendant
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-s", "a", "--before", "b"]);
     insta::assert_snapshot!(stderr, @r###"
-    error: the argument '--source <SOURCE>' cannot be used with '--insert-before <INSERT_BEFORE>'
-
-    Usage: jj rebase --source <SOURCE> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>>
-
-    For more information, try '--help'.
+    Error: Cannot insert the rebased commits next to 1394f625cbbd, since it is one of them
+    Hint: The destination is part of the commits being rebased. Use `jj rebase -r` to rebase a revision without its descendants.
     "###);
 
-    // -b with --before
-    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["rebase", "-b", "a", "--before", "b"]);
+    // -b with --before a descendant
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-b", "a", "--before", "b"]);
     insta::assert_snapshot!(stderr, @r###"
-    error: the argument '--branch <BRANCH>' cannot be used with '--insert-before <INSERT_BEFORE>'
+    Error: Branch resolved to no commits to rebase
+    Hint: Pass `--allow-empty-selection` to rebase nothing without an error.
+    "###);
 
-    Usage: jj rebase --branch <BRANCH> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>>
+    // -b with --before the root commit
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-b", "@", "-B", "root()"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: The root commit 000000000000 is immutable
+    "###);
 
-    For more information, try '--help'.
+    // -s with --before the root commit
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-s", "b", "-B", "root()"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: The root commit 000000000000 is immutable
     "###);
 
     // Rebase onto self with -r
@@ -156,15 +143,57 @@ fn test_rebase_invalid() {
     "###);
 
     // Rebase root with -r
-    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-r", "root()", "-d", "a"]);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-r", "root()", "-d", "@"]);
     insta::assert_snapshot!(stderr, @r###"
-    Error: The root commit 000000000000 is immutable
+    Error: Cannot rebase the root commit
+    "###);
+
+    // Rebase root onto itself with -r
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-r", "root()", "-d", "root()"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot rebase 000000000000 onto itself
+    "###);
+
+    // Rebase root with -s
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-s", "root()", "-d", "a"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot rebase the root commit
     "###);
 
     // Rebase onto descendant with -s
     let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-s", "a", "-d", "b"]);
     insta::assert_snapshot!(stderr, @r###"
     Error: Cannot rebase 2443ea76b0b1 onto descendant 1394f625cbbd
+    Hint: The destination is part of the commits being rebased. Use `jj rebase -r` to rebase a revision without its descendants.
+    "###);
+
+    // Rebase onto self with -s
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-s", "a", "-d", "a"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot rebase 2443ea76b0b1 onto itself
+    Hint: The destination is part of the commits being rebased. Use `jj rebase -r` to rebase a revision without its descendants.
+    "###);
+
+    // Rebase onto descendant of another source with -s. All sources which can't
+    // be rebased are reported.
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["rebase", "-s", "a", "-s", "b", "-d", "b", "-d", "root()"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Warning: Ignoring redundant destination 000000000000 (ancestor of 1394f625cbbd)
+    Error: Cannot rebase 2 commits onto their destinations:
+      2443ea76b0b1 onto descendant 1394f625cbbd
+      1394f625cbbd onto itself
+    Hint: The destination is part of the commits being rebased. Use `jj rebase -r` to rebase a revision without its descendants.
+    "###);
+
+    // A destination which is an ancestor of another one is ignored
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-s", "a", "-d", "a", "-d", "b"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Warning: Ignoring redundant destination 2443ea76b0b1 (ancestor of 1394f625cbbd)
+    Error: Cannot rebase 2443ea76b0b1 onto descendant 1394f625cbbd
+    Hint: The destination is part of the commits being rebased. Use `jj rebase -r` to rebase a revision without its descendants.
     "###);
 }
 
@@ -783,29 +812,288 @@ fn test_rebase_multiple_destinations() {
     ◉
     "###);
 
-    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-r", "a", "-d", "b", "-d", "b"]);
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "a", "-d", "b", "-d", "b"]);
+    insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Error: More than one revset resolved to revision d370aee184ba
+    Warning: Ignoring repeated destination d370aee184ba
+    Rebased 1 commits onto destination
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  a
+    ◉  b
+    │ @  c
+    ├─╯
+    ◉
     "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
 
-    // Same error with 'all:' if there is overlap.
-    let stderr = test_env.jj_cmd_failure(
+    // Same with 'all:' if there is overlap.
+    let (stdout, stderr) = test_env.jj_cmd_ok(
         &repo_path,
         &["rebase", "-r", "a", "-d", "all:b|c", "-d", "b"],
     );
+    insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Error: More than one revset resolved to revision d370aee184ba
+    Warning: Ignoring repeated destination d370aee184ba
+    Skipped rebase of 1 commits that were already in place
     "###);
 
-    let stderr = test_env.jj_cmd_failure(
+    // The root commit is an ancestor of the other destination, so it's ignored
+    // instead of creating a merge with it.
+    let (stdout, stderr) = test_env.jj_cmd_ok(
         &repo_path,
         &["rebase", "-r", "a", "-d", "b", "-d", "root()"],
     );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Warning: Ignoring redundant destination 000000000000 (ancestor of d370aee184ba)
+    Rebased 1 commits onto destination
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  a
+    ◉  b
+    │ @  c
+    ├─╯
+    ◉
+    "###);
+}
+
+#[test]
+fn test_rebase_redundant_destinations() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &[]);
+    create_commit(&test_env, &repo_path, "d", &[]);
+    test_env.jj_cmd_ok(&repo_path, &["edit", "a"]);
+    let get_commit_id = |revision: &str| {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "log",
+                "--no-graph",
+                "-r",
+                revision,
+                "-T",
+                "commit_id.short()",
+            ],
+        )
+    };
+    let get_parent_branches = |revision: &str| {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "log",
+                "--no-graph",
+                "-r",
+                &format!("{revision}-"),
+                "-T",
+                r#"branches ++ "\n""#,
+            ],
+        )
+    };
+    let a_id = get_commit_id("a");
+    let b_id = get_commit_id("b");
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  d
+    │ ◉  c
+    ├─╯
+    │ ◉  b
+    │ @  a
+    ├─╯
+    ◉
+    "###);
+
+    // a is an ancestor of b, so c is only rebased onto b
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "c", "-d", "a", "-d", "b"]);
+    assert_eq!(stdout, "");
+    assert_eq!(
+        stderr,
+        format!(
+            "Warning: Ignoring redundant destination {a_id} (ancestor of {b_id})\n\
+             Rebased 1 commits onto destination\n"
+        )
+    );
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  c
+    ◉  b
+    @  a
+    │ ◉  d
+    ├─╯
+    ◉
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // Same with --insert-after together with --insert-before
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase", "-r", "c", "--after", "a", "--after", "b", "--before", "d",
+        ],
+    );
+    assert_eq!(stdout, "");
+    assert_eq!(
+        stderr,
+        format!(
+            "Warning: Ignoring redundant destination {a_id} (ancestor of {b_id})\n\
+             Rebased 1 commits onto destination\n\
+             Rebased 1 descendant commits\n"
+        )
+    );
+    assert_eq!(get_parent_branches("c"), "b\n");
+    assert_eq!(get_parent_branches("d"), "c\n");
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // With --insert-before, the parents of the new children are deduplicated,
+    // and the new children don't keep the ignored parents either
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-r", "c", "--before", "b", "--before", "d"],
+    );
+    assert_eq!(stdout, "");
+    assert_eq!(
+        stderr,
+        format!(
+            "Warning: Ignoring redundant destination 000000000000 (ancestor of {a_id})\n\
+             Rebased 1 commits onto destination\n\
+             Rebased 2 descendant commits\n"
+        )
+    );
+    assert_eq!(get_parent_branches("c"), "a\n");
+    assert_eq!(get_parent_branches("b"), "c\n");
+    assert_eq!(get_parent_branches("d"), "c\n");
+
+    // Inserting after a commit and its ancestor would create a loop
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["rebase", "-r", "c", "--after", "a", "--after", "b"],
+    );
+    assert_eq!(
+        stderr,
+        format!(
+            "Error: Refusing to create a loop: commit {b_id} would be both an ancestor and a \
+             descendant of the rebased commits\n"
+        )
+    );
+}
+
+#[test]
+fn test_rebase_destination_already_parent() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &[]);
+    create_commit(&test_env, &repo_path, "c", &["a"]);
+    create_commit(&test_env, &repo_path, "d", &[]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  d
+    │ ◉  c
+    │ ◉  a
+    ├─╯
+    │ ◉  b
+    ├─╯
+    ◉
+    "###);
+
+    // The existing parent "a" is kept once, and "b" is added as a new parent.
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "c", "-d", "a", "-d", "b"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉    c
+    ├─╮
+    │ ◉  b
+    ◉ │  a
+    ├─╯
+    │ @  d
+    ├─╯
+    ◉
+    "###);
+
+    // Same with -s
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "c", "-d", "a", "-d", "b"]);
+    insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Error: The Git backend does not support creating merge commits with the root commit as one of the parents.
+    Rebased 1 commits
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉    c
+    ├─╮
+    │ ◉  b
+    ◉ │  a
+    ├─╯
+    │ @  d
+    ├─╯
+    ◉
     "###);
 }
 
+#[test]
+fn test_rebase_destination_order() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let get_parents = |revision: &str| {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "log",
+                "--no-graph",
+                "-r",
+                revision,
+                "-T",
+                r#"parents.map(|c| c.description().first_line()).join(" ")"#,
+            ],
+        )
+    };
+
+    create_commit(&test_env, &repo_path, "j", &[]);
+    create_commit(&test_env, &repo_path, "k", &["j"]);
+    create_commit(&test_env, &repo_path, "m", &[]);
+    create_commit(&test_env, &repo_path, "l", &["j"]);
+
+    // The first destination becomes the first parent
+    test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "l", "-d", "k", "-d", "m"]);
+    assert_eq!(get_parents("l"), "k m");
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "l", "-d", "m", "-d", "k"]);
+    assert_eq!(get_parents("l"), "m k");
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "l", "-d", "m", "-d", "k"]);
+    assert_eq!(get_parents("l"), "m k");
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // Repeated destinations are left out after their first occurrence
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-s", "l", "-d", "m", "-d", "k", "-d", "m"],
+    );
+    assert_eq!(get_parents("l"), "m k");
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // Redundant destinations are left out without reordering the others
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-s", "l", "-d", "j", "-d", "m", "-d", "k"],
+    );
+    assert_eq!(get_parents("l"), "m k");
+}
+
 #[test]
 fn test_rebase_with_descendants() {
     let test_env = TestEnvironment::default();
@@ -927,6 +1215,60 @@ fn test_rebase_with_descendants() {
     "###);
 }
 
+#[test]
+fn test_rebase_with_descendants_and_reorder() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    create_commit(&test_env, &repo_path, "d", &["c"]);
+    create_commit(&test_env, &repo_path, "e", &[]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  e
+    │ ◉  d
+    │ ◉  c
+    │ ◉  b
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+
+    // The chain is reversed: "c" is moved onto "d" and then "b" onto "c", and
+    // "d" fills the holes left behind, ending up on "e".
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-s", "b", "-d", "e", "--reorder", "d,c,b"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 3 commits
+    Reordered 2 commits
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  b
+    ◉  c
+    ◉  d
+    @  e
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+
+    // Only commits in the rebased subtree can be reordered
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["rebase", "-s", "b", "-d", "e", "--reorder", "c,a"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot reorder 2443ea76b0b1, which is not part of the rebased commits
+    "###);
+}
+
 #[test]
 fn test_rebase_error_revision_does_not_exist() {
     let test_env = TestEnvironment::default();
@@ -948,250 +1290,421 @@ fn test_rebase_error_revision_does_not_exist() {
     "###);
 }
 
-fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
-    test_env.jj_cmd_success(repo_path, &["log", "-T", "branches"])
-}
-
-// This behavior illustrates https://github.com/martinvonz/jj/issues/2600
 #[test]
-fn test_rebase_with_child_and_descendant_bug_2600() {
+fn test_rebase_targets_file() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
 
-    create_commit(&test_env, &repo_path, "notroot", &[]);
-    create_commit(&test_env, &repo_path, "base", &["notroot"]);
-    create_commit(&test_env, &repo_path, "a", &["base"]);
-    create_commit(&test_env, &repo_path, "b", &["base", "a"]);
-    create_commit(&test_env, &repo_path, "c", &["b"]);
-    let setup_opid = test_env.current_operation_id(&repo_path);
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &[]);
+    create_commit(&test_env, &repo_path, "c", &[]);
+    create_commit(&test_env, &repo_path, "e", &[]);
+    let plan_path = test_env.env_root().join("plan");
 
-    // Test the setup
-    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    @  c
-    ◉    b
-    ├─╮
-    │ ◉  a
-    ├─╯
-    ◉  base
-    ◉  notroot
-    ◉
-    "###);
-
-    // ===================== rebase -s tests =================
-    let (stdout, stderr) =
-        test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "base", "-d", "notroot"]);
+    std::fs::write(&plan_path, "# Move b and c onto a\nb\n\nc\n").unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "--targets-file",
+            "../plan",
+            "--targets-mode",
+            "revisions",
+            "-d",
+            "a",
+        ],
+    );
     insta::assert_snapshot!(stdout, @"");
-    // This should be a no-op
     insta::assert_snapshot!(stderr, @r###"
-    Skipped rebase of 1 commits that were already in place
+    Rebased 2 commits onto destination
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    @  c
-    ◉    b
-    ├─╮
-    │ ◉  a
+    ◉  c
+    │ ◉  b
+    ├─╯
+    ◉  a
+    │ @  e
     ├─╯
-    ◉  base
-    ◉  notroot
     ◉
     "###);
 
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "a", "-d", "base"]);
-    insta::assert_snapshot!(stdout, @"");
-    // This should be a no-op
+    // Errors point to the offending line
+    std::fs::write(&plan_path, "b\nnonexistent\n").unwrap();
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "rebase",
+            "--targets-file",
+            "../plan",
+            "--targets-mode",
+            "source",
+            "-d",
+            "root()",
+        ],
+    );
     insta::assert_snapshot!(stderr, @r###"
-    Skipped rebase of 1 commits that were already in place
-    "###);
-    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    @  c
-    ◉    b
-    ├─╮
-    │ ◉  a
-    ├─╯
-    ◉  base
-    ◉  notroot
-    ◉
+    Error: Revision "nonexistent" doesn't exist
+    Hint: The revset is on line 2 of ../plan
     "###);
 
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "a", "-d", "root()"]);
-    insta::assert_snapshot!(stdout, @"");
+    // The file must contain at least one revset
+    std::fs::write(&plan_path, "# Nothing to do\n").unwrap();
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "rebase",
+            "--targets-file",
+            "../plan",
+            "--targets-mode",
+            "branch",
+            "-d",
+            "root()",
+        ],
+    );
     insta::assert_snapshot!(stderr, @r###"
-    Rebased 3 commits
-    Working copy now at: znkkpsqq cf8ecff5 c | c
-    Parent commit      : vruxwmqv 24e1a270 b | b
-    "###);
-    // Commit "a" should be rebased onto the root commit. Commit "b" should have
-    // "base" and "a" as parents as before.
-    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    @  c
-    ◉    b
-    ├─╮
-    │ ◉  a
-    ◉ │  base
-    ◉ │  notroot
-    ├─╯
-    ◉
+    Error: No revsets found in ../plan
     "###);
+}
 
-    // ===================== rebase -b tests =================
-    // ====== Reminder of the setup =========
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+#[test]
+fn test_rebase_targets_file_flags() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let edit_script = test_env.set_up_fake_editor();
+    let plan_path = test_env.env_root().join("plan");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    std::fs::write(repo_path.join("other"), "other\n").unwrap();
+    create_commit(&test_env, &repo_path, "e", &[]);
+    // Test the setup
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    @  c
-    ◉    b
-    ├─╮
+    @  e
+    │ ◉  c
+    │ ◉  b
     │ ◉  a
     ├─╯
-    ◉  base
-    ◉  notroot
     ◉
     "###);
 
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-b", "c", "-d", "base"]);
-    insta::assert_snapshot!(stdout, @"");
-    // The commits in roots(base..c), i.e. commit "a" should be rebased onto "base",
-    // which is a no-op
+    // --skip can't be used in revisions mode, like with -r
+    std::fs::write(&plan_path, "b\n").unwrap();
+    let stderr = test_env.jj_cmd_cli_error(
+        &repo_path,
+        &[
+            "rebase",
+            "--targets-file",
+            "../plan",
+            "--targets-mode",
+            "revisions",
+            "-d",
+            "e",
+            "--skip",
+            "c",
+        ],
+    );
     insta::assert_snapshot!(stderr, @r###"
-    Skipped rebase of 1 commits that were already in place
-    "###);
-    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    @  c
-    ◉    b
-    ├─╮
-    │ ◉  a
-    ├─╯
-    ◉  base
-    ◉  notroot
-    ◉
+    Error: --skip cannot be used with --targets-mode revisions
     "###);
 
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-b", "c", "-d", "a"]);
-    insta::assert_snapshot!(stdout, @"");
+    // Flags which only work with -r only work in revisions mode
+    let stderr = test_env.jj_cmd_cli_error(
+        &repo_path,
+        &[
+            "rebase",
+            "--targets-file",
+            "../plan",
+            "--targets-mode",
+            "branch",
+            "-d",
+            "e",
+            "--split-paths",
+            "c",
+        ],
+    );
     insta::assert_snapshot!(stderr, @r###"
-    Rebased 2 commits
-    Working copy now at: znkkpsqq 76914dcc c | c
-    Parent commit      : vruxwmqv f73f03c7 b | b
+    Error: --split-paths only works with --targets-mode revisions
     "###);
-    // The commits in roots(a..c), i.e. commit "b" should be rebased onto "a",
-    // which means "b" loses its "base" parent
-    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    @  c
-    ◉  b
-    ◉  a
-    ◉  base
-    ◉  notroot
-    ◉
+
+    // --reorder only works in source mode
+    let stderr = test_env.jj_cmd_cli_error(
+        &repo_path,
+        &[
+            "rebase",
+            "--targets-file",
+            "../plan",
+            "--targets-mode",
+            "revisions",
+            "-d",
+            "e",
+            "--reorder",
+            "c,b",
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: --reorder only works with --targets-mode source
     "###);
 
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-b", "a", "-d", "root()"]);
+    // --move-descendants works in revisions mode
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "--targets-file",
+            "../plan",
+            "--targets-mode",
+            "revisions",
+            "-d",
+            "e",
+            "--move-descendants",
+        ],
+    );
     insta::assert_snapshot!(stdout, @"");
-    // This should be a no-op
     insta::assert_snapshot!(stderr, @r###"
-    Skipped rebase of 1 commits that were already in place
+    Rebased 1 commits onto destination
+    Rebased 1 descendant commits
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    @  c
-    ◉    b
-    ├─╮
+    ◉  c
+    ◉  b
+    @  e
     │ ◉  a
     ├─╯
-    ◉  base
-    ◉  notroot
     ◉
     "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
 
-    // ===================== rebase -r tests =================
-    // ====== Reminder of the setup =========
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+    // --reorder works in source mode
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "--targets-file",
+            "../plan",
+            "--targets-mode",
+            "source",
+            "-d",
+            "e",
+            "--reorder",
+            "c,b",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 2 commits
+    Reordered 1 commits
+    "###);
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    @  c
-    ◉    b
-    ├─╮
+    ◉  b
+    ◉  c
+    @  e
     │ ◉  a
     ├─╯
-    ◉  base
-    ◉  notroot
     ◉
     "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
 
-    let (stdout, stderr) =
-        test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "base", "-d", "root()"]);
+    // --message-template works in revisions mode
+    std::fs::write(&plan_path, "c\n").unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "--targets-file",
+            "../plan",
+            "--targets-mode",
+            "revisions",
+            "-d",
+            "a",
+            "--message-template",
+            r#""PROJ-1: " ++ description"#,
+        ],
+    );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits onto destination
-    Rebased 3 descendant commits
-    Working copy now at: znkkpsqq 45371aaf c | c
-    Parent commit      : vruxwmqv c0a76bf4 b | b
-    Added 0 files, modified 0 files, removed 1 files
     "###);
-    // The user would expect unsimplified ancestry here.
-    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    @  c
-    ◉    b
-    ├─╮
-    │ ◉  a
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "-T",
+            r#"separate(" ", branches, description.first_line())"#,
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    ◉  c PROJ-1: c
+    │ ◉  b b
     ├─╯
-    ◉  notroot
-    │ ◉  base
+    ◉  a a
+    │ @  e e
     ├─╯
     ◉
     "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
 
-    // This tests the algorithm for rebasing onto descendants. The result should
-    // have unsimplified ancestry.
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "base", "-d", "b"]);
+    // --split-paths works in revisions mode
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "--targets-file",
+            "../plan",
+            "--targets-mode",
+            "revisions",
+            "-d",
+            "a",
+            "--split-paths",
+            "c",
+        ],
+    );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits onto destination
-    Rebased 3 descendant commits
-    Working copy now at: znkkpsqq e28fa972 c | c
-    Parent commit      : vruxwmqv 8d0eeb6a b | b
-    Added 0 files, modified 0 files, removed 1 files
+    Split 1 commits at the given paths
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    @  c
-    │ ◉  base
+    ◉  c
+    ◉
+    │ ◉  b
     ├─╯
-    ◉    b
-    ├─╮
-    │ ◉  a
+    ◉  a
+    │ @  e
     ├─╯
-    ◉  notroot
     ◉
     "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
 
-    // This tests the algorithm for rebasing onto descendants. The result should
-    // have unsimplified ancestry.
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "base", "-d", "a"]);
+    // --interactive works in revisions mode
+    let change_c = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r", "c", "-T", "change_id.short()"],
+    );
+    std::fs::write(&edit_script, format!("write\n{change_c}: e\n")).unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "--targets-file",
+            "../plan",
+            "--targets-mode",
+            "revisions",
+            "-i",
+        ],
+    );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits onto destination
-    Rebased 3 descendant commits
-    Working copy now at: znkkpsqq a9da974c c | c
-    Parent commit      : vruxwmqv 0072139c b | b
-    Added 0 files, modified 0 files, removed 1 files
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    @  c
-    ◉    b
-    ├─╮
-    │ │ ◉  base
-    │ ├─╯
+    ◉  c
+    @  e
+    │ ◉  b
     │ ◉  a
     ├─╯
-    ◉  notroot
     ◉
     "###);
+}
 
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
-    // ====== Reminder of the setup =========
+#[test]
+fn test_rebase_auto_destination() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &[]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    create_commit(&test_env, &repo_path, "e", &[]);
+
+    // The default trunk() falls back to the root commit without a remote
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-b", "c", "--auto-destination"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: The trunk() revision resolved to the root commit
+    Hint: Set `revset-aliases."trunk()"` to your trunk branch, e.g. `main@origin`.
+    "###);
+
+    test_env.add_config(r#"revset-aliases."trunk()" = "a""#);
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["rebase", "-b", "c", "--auto-destination"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 2 commits
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  c
+    ◉  b
+    ◉  a
+    │ @  e
+    ├─╯
+    ◉
+    "###);
+}
+
+#[test]
+fn test_rebase_empty_selection() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-r", "none()", "-d", "a"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Source revset resolved to no commits
+    Hint: Pass `--allow-empty-selection` to rebase nothing without an error.
+    "###);
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "-r",
+            "none()",
+            "-d",
+            "a",
+            "--allow-empty-selection",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @"");
+
+    // "a" is already an ancestor of the destination
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-b", "a", "-d", "b"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Branch resolved to no commits to rebase
+    Hint: Pass `--allow-empty-selection` to rebase nothing without an error.
+    "###);
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-b", "a", "-d", "b", "--allow-empty-selection"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @"");
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    test_env.jj_cmd_success(repo_path, &["log", "-T", "branches"])
+}
+
+// This behavior illustrates https://github.com/martinvonz/jj/issues/2600
+#[test]
+fn test_rebase_with_child_and_descendant_bug_2600() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "notroot", &[]);
+    create_commit(&test_env, &repo_path, "base", &["notroot"]);
+    create_commit(&test_env, &repo_path, "a", &["base"]);
+    create_commit(&test_env, &repo_path, "b", &["base", "a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    let setup_opid = test_env.current_operation_id(&repo_path);
+
+    // Test the setup
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
     @  c
     ◉    b
@@ -1203,67 +1716,74 @@ fn test_rebase_with_child_and_descendant_bug_2600() {
     ◉
     "###);
 
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "a", "-d", "root()"]);
+    // ===================== rebase -s tests =================
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "base", "-d", "notroot"]);
     insta::assert_snapshot!(stdout, @"");
+    // This should be a no-op
     insta::assert_snapshot!(stderr, @r###"
-    Rebased 1 commits onto destination
-    Rebased 2 descendant commits
-    Working copy now at: znkkpsqq 7210b05e c | c
-    Parent commit      : vruxwmqv da3f7511 b | b
-    Added 0 files, modified 0 files, removed 1 files
+    Skipped rebase of 1 commits that were already in place
+    Nothing changed.
     "###);
-    // In this case, it is unclear whether the user would always prefer unsimplified
-    // ancestry (whether `b` should also be a direct child of the root commit).
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
     @  c
-    ◉  b
-    ◉  base
-    ◉  notroot
+    ◉    b
+    ├─╮
     │ ◉  a
     ├─╯
+    ◉  base
+    ◉  notroot
     ◉
     "###);
 
     test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "b", "-d", "root()"]);
+    let op_id = test_env.current_operation_id(&repo_path);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "a", "-d", "base"]);
     insta::assert_snapshot!(stdout, @"");
+    // This should be a no-op, which doesn't record an operation
     insta::assert_snapshot!(stderr, @r###"
-    Rebased 1 commits onto destination
-    Rebased 1 descendant commits
-    Working copy now at: znkkpsqq f280545e c | c
-    Parent commit      : zsuskuln 0a7fb8f6 base | base
-    Parent commit      : royxmykx 86a06598 a | a
-    Added 0 files, modified 0 files, removed 1 files
+    Skipped rebase of 1 commits that were already in place
+    Nothing changed.
     "###);
-    // The user would expect unsimplified ancestry here.
+    assert_eq!(test_env.current_operation_id(&repo_path), op_id);
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    @    c
+    @  c
+    ◉    b
     ├─╮
     │ ◉  a
     ├─╯
     ◉  base
     ◉  notroot
-    │ ◉  b
-    ├─╯
     ◉
     "###);
 
-    // This tests the algorithm for rebasing onto descendants. The result should
-    // have unsimplified ancestry.
     test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "b", "-d", "c"]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "a", "-d", "root()"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Rebased 1 commits onto destination
-    Rebased 1 descendant commits
-    Working copy now at: znkkpsqq c0a7cd80 c | c
-    Parent commit      : zsuskuln 0a7fb8f6 base | base
-    Parent commit      : royxmykx 86a06598 a | a
-    Added 0 files, modified 0 files, removed 1 files
+    Rebased 3 commits
+    Working copy now at: znkkpsqq cf8ecff5 c | c
+    Parent commit      : vruxwmqv 24e1a270 b | b
     "###);
+    // Commit "a" should be rebased onto the root commit. Commit "b" should have
+    // "base" and "a" as parents as before.
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
-    ◉  b
-    @    c
+    @  c
+    ◉    b
+    ├─╮
+    │ ◉  a
+    ◉ │  base
+    ◉ │  notroot
+    ├─╯
+    ◉
+    "###);
+
+    // ===================== rebase -b tests =================
+    // ====== Reminder of the setup =========
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  c
+    ◉    b
     ├─╮
     │ ◉  a
     ├─╯
@@ -1272,236 +1792,274 @@ fn test_rebase_with_child_and_descendant_bug_2600() {
     ◉
     "###);
 
-    // In this test, the commit with weird ancestry is not rebased (neither directly
-    // nor indirectly).
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "c", "-d", "a"]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-b", "c", "-d", "base"]);
     insta::assert_snapshot!(stdout, @"");
+    // The commits in roots(base..c), i.e. commit "a" should be rebased onto "base",
+    // which is a no-op
     insta::assert_snapshot!(stderr, @r###"
-    Rebased 1 commits onto destination
-    Working copy now at: znkkpsqq 7a3bc050 c | c
-    Parent commit      : royxmykx 86a06598 a | a
-    Added 0 files, modified 0 files, removed 1 files
+    Skipped rebase of 1 commits that were already in place
+    Nothing changed.
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
     @  c
-    │ ◉  b
-    ╭─┤
-    ◉ │  a
+    ◉    b
+    ├─╮
+    │ ◉  a
     ├─╯
     ◉  base
     ◉  notroot
     ◉
     "###);
-}
-
-#[test]
-fn test_rebase_revisions_after() {
-    let test_env = TestEnvironment::default();
-    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
-    let repo_path = test_env.env_root().join("repo");
-
-    create_commit(&test_env, &repo_path, "a", &[]);
-    create_commit(&test_env, &repo_path, "b1", &["a"]);
-    create_commit(&test_env, &repo_path, "b2", &["b1"]);
-    create_commit(&test_env, &repo_path, "b3", &["a"]);
-    create_commit(&test_env, &repo_path, "b4", &["b3"]);
-    create_commit(&test_env, &repo_path, "c", &["b2", "b4"]);
-    create_commit(&test_env, &repo_path, "d", &["c"]);
-    create_commit(&test_env, &repo_path, "e", &["c"]);
-    create_commit(&test_env, &repo_path, "f", &["e"]);
-    // Test the setup
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    @  f  xznxytkn  e4a00798
-    ◉  e  nkmrtpmo  858693f7
-    │ ◉  d  lylxulpl  7d0512e5
-    ├─╯
-    ◉    c  kmkuslsw  cd86b3e4
-    ├─╮
-    │ ◉  b4  znkkpsqq  a52a83a4
-    │ ◉  b3  vruxwmqv  523e6a8b
-    ◉ │  b2  royxmykx  2b8e1148
-    ◉ │  b1  zsuskuln  072d5ae1
-    ├─╯
-    ◉  a  rlvkpnrz  2443ea76
-    ◉    zzzzzzzz  00000000
-    "###);
-    let setup_opid = test_env.current_operation_id(&repo_path);
 
-    // Rebasing a commit after its parents should be a no-op.
-    let (stdout, stderr) = test_env.jj_cmd_ok(
-        &repo_path,
-        &["rebase", "-r", "c", "--after", "b2", "--after", "b4"],
-    );
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-b", "c", "-d", "a"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Skipped rebase of 4 commits that were already in place
-    Nothing changed.
+    Rebased 2 commits
+    Working copy now at: znkkpsqq 76914dcc c | c
+    Parent commit      : vruxwmqv f73f03c7 b | b
     "###);
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    @  f  xznxytkn  e4a00798
-    ◉  e  nkmrtpmo  858693f7
-    │ ◉  d  lylxulpl  7d0512e5
-    ├─╯
-    ◉    c  kmkuslsw  cd86b3e4
-    ├─╮
-    │ ◉  b4  znkkpsqq  a52a83a4
-    │ ◉  b3  vruxwmqv  523e6a8b
-    ◉ │  b2  royxmykx  2b8e1148
-    ◉ │  b1  zsuskuln  072d5ae1
-    ├─╯
-    ◉  a  rlvkpnrz  2443ea76
-    ◉    zzzzzzzz  00000000
+    // The commits in roots(a..c), i.e. commit "b" should be rebased onto "a",
+    // which means "b" loses its "base" parent
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  c
+    ◉  b
+    ◉  a
+    ◉  base
+    ◉  notroot
+    ◉
     "###);
 
-    // Rebasing a commit after itself should be a no-op.
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "c", "--after", "c"]);
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-b", "a", "-d", "root()"]);
     insta::assert_snapshot!(stdout, @"");
+    // This should be a no-op
     insta::assert_snapshot!(stderr, @r###"
-    Skipped rebase of 4 commits that were already in place
+    Skipped rebase of 1 commits that were already in place
     Nothing changed.
     "###);
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    @  f  xznxytkn  e4a00798
-    ◉  e  nkmrtpmo  858693f7
-    │ ◉  d  lylxulpl  7d0512e5
-    ├─╯
-    ◉    c  kmkuslsw  cd86b3e4
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  c
+    ◉    b
     ├─╮
-    │ ◉  b4  znkkpsqq  a52a83a4
-    │ ◉  b3  vruxwmqv  523e6a8b
-    ◉ │  b2  royxmykx  2b8e1148
-    ◉ │  b1  zsuskuln  072d5ae1
+    │ ◉  a
     ├─╯
-    ◉  a  rlvkpnrz  2443ea76
-    ◉    zzzzzzzz  00000000
+    ◉  base
+    ◉  notroot
+    ◉
     "###);
 
-    // Rebase a commit after another commit. "c" has parents "b2" and "b4", so its
-    // children "d" and "e" should be rebased onto "b2" and "b4" respectively.
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "c", "--after", "e"]);
+    // ===================== rebase -r tests =================
+    // ====== Reminder of the setup =========
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  c
+    ◉    b
+    ├─╮
+    │ ◉  a
+    ├─╯
+    ◉  base
+    ◉  notroot
+    ◉
+    "###);
+
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "base", "-d", "root()"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits onto destination
     Rebased 3 descendant commits
-    Working copy now at: xznxytkn e0e873c8 f | f
-    Parent commit      : kmkuslsw 754793f3 c | c
+    Working copy now at: znkkpsqq 45371aaf c | c
+    Parent commit      : vruxwmqv c0a76bf4 b | b
+    Added 0 files, modified 0 files, removed 1 files
     "###);
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    @  f  xznxytkn  e0e873c8
-    ◉  c  kmkuslsw  754793f3
-    ◉    e  nkmrtpmo  e0d7fb63
+    // The user would expect unsimplified ancestry here.
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  c
+    ◉    b
     ├─╮
-    │ │ ◉  d  lylxulpl  5e9cb58d
-    ╭─┬─╯
-    │ ◉  b4  znkkpsqq  a52a83a4
-    │ ◉  b3  vruxwmqv  523e6a8b
-    ◉ │  b2  royxmykx  2b8e1148
-    ◉ │  b1  zsuskuln  072d5ae1
+    │ ◉  a
     ├─╯
-    ◉  a  rlvkpnrz  2443ea76
-    ◉    zzzzzzzz  00000000
+    ◉  notroot
+    │ ◉  base
+    ├─╯
+    ◉
     "###);
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
 
-    // Rebase a commit after a leaf commit.
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "e", "--after", "f"]);
+    // This tests the algorithm for rebasing onto descendants. The result should
+    // have unsimplified ancestry.
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "base", "-d", "b"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits onto destination
-    Rebased 1 descendant commits
-    Working copy now at: xznxytkn 9804b742 f | f
-    Parent commit      : kmkuslsw cd86b3e4 c | c
+    Rebased 3 descendant commits
+    Working copy now at: znkkpsqq e28fa972 c | c
+    Parent commit      : vruxwmqv 8d0eeb6a b | b
     Added 0 files, modified 0 files, removed 1 files
     "###);
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    ◉  e  nkmrtpmo  76ac6464
-    @  f  xznxytkn  9804b742
-    │ ◉  d  lylxulpl  7d0512e5
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  c
+    │ ◉  base
     ├─╯
-    ◉    c  kmkuslsw  cd86b3e4
+    ◉    b
     ├─╮
-    │ ◉  b4  znkkpsqq  a52a83a4
-    │ ◉  b3  vruxwmqv  523e6a8b
-    ◉ │  b2  royxmykx  2b8e1148
-    ◉ │  b1  zsuskuln  072d5ae1
+    │ ◉  a
     ├─╯
-    ◉  a  rlvkpnrz  2443ea76
-    ◉    zzzzzzzz  00000000
+    ◉  notroot
+    ◉
     "###);
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
 
-    // Rebase a commit after a commit in a branch of a merge commit.
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "f", "--after", "b1"]);
+    // This tests the algorithm for rebasing onto descendants. The result should
+    // have unsimplified ancestry.
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "base", "-d", "a"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits onto destination
-    Rebased 4 descendant commits
-    Working copy now at: xznxytkn 80c27408 f | f
-    Parent commit      : zsuskuln 072d5ae1 b1 | b1
-    Added 0 files, modified 0 files, removed 5 files
+    Rebased 3 descendant commits
+    Working copy now at: znkkpsqq a9da974c c | c
+    Parent commit      : vruxwmqv 0072139c b | b
+    Added 0 files, modified 0 files, removed 1 files
     "###);
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    ◉  e  nkmrtpmo  cee7a197
-    │ ◉  d  lylxulpl  1eb960ec
-    ├─╯
-    ◉    c  kmkuslsw  305a7803
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  c
+    ◉    b
     ├─╮
-    │ ◉  b4  znkkpsqq  a52a83a4
-    │ ◉  b3  vruxwmqv  523e6a8b
-    ◉ │  b2  royxmykx  526481b4
-    @ │  f  xznxytkn  80c27408
-    ◉ │  b1  zsuskuln  072d5ae1
+    │ │ ◉  base
+    │ ├─╯
+    │ ◉  a
     ├─╯
-    ◉  a  rlvkpnrz  2443ea76
-    ◉    zzzzzzzz  00000000
+    ◉  notroot
+    ◉
     "###);
+
     test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+    // ====== Reminder of the setup =========
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  c
+    ◉    b
+    ├─╮
+    │ ◉  a
+    ├─╯
+    ◉  base
+    ◉  notroot
+    ◉
+    "###);
 
-    // Rebase a commit after the last commit in a branch of a merge commit.
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "f", "--after", "b2"]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "a", "-d", "root()"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits onto destination
-    Rebased 3 descendant commits
-    Working copy now at: xznxytkn ebbc24b1 f | f
-    Parent commit      : royxmykx 2b8e1148 b2 | b2
-    Added 0 files, modified 0 files, removed 4 files
+    Rebased 2 descendant commits
+    Working copy now at: znkkpsqq 7210b05e c | c
+    Parent commit      : vruxwmqv da3f7511 b | b
+    Added 0 files, modified 0 files, removed 1 files
     "###);
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    ◉  e  nkmrtpmo  3162ac52
-    │ ◉  d  lylxulpl  6f7f3b2a
+    // In this case, it is unclear whether the user would always prefer unsimplified
+    // ancestry (whether `b` should also be a direct child of the root commit).
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  c
+    ◉  b
+    ◉  base
+    ◉  notroot
+    │ ◉  a
     ├─╯
-    ◉    c  kmkuslsw  d33f69f1
+    ◉
+    "###);
+
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "b", "-d", "root()"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Rebased 1 descendant commits
+    Working copy now at: znkkpsqq f280545e c | c
+    Parent commit      : zsuskuln 0a7fb8f6 base | base
+    Parent commit      : royxmykx 86a06598 a | a
+    Added 0 files, modified 0 files, removed 1 files
+    "###);
+    // The user would expect unsimplified ancestry here.
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @    c
     ├─╮
-    │ @  f  xznxytkn  ebbc24b1
-    │ ◉  b2  royxmykx  2b8e1148
-    │ ◉  b1  zsuskuln  072d5ae1
-    ◉ │  b4  znkkpsqq  a52a83a4
-    ◉ │  b3  vruxwmqv  523e6a8b
+    │ ◉  a
     ├─╯
-    ◉  a  rlvkpnrz  2443ea76
-    ◉    zzzzzzzz  00000000
+    ◉  base
+    ◉  notroot
+    │ ◉  b
+    ├─╯
+    ◉
     "###);
+
+    // This tests the algorithm for rebasing onto descendants. The result should
+    // have unsimplified ancestry.
     test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "b", "-d", "c"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Rebased 1 descendant commits
+    Working copy now at: znkkpsqq c0a7cd80 c | c
+    Parent commit      : zsuskuln 0a7fb8f6 base | base
+    Parent commit      : royxmykx 86a06598 a | a
+    Added 0 files, modified 0 files, removed 1 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  b
+    @    c
+    ├─╮
+    │ ◉  a
+    ├─╯
+    ◉  base
+    ◉  notroot
+    ◉
+    "###);
 
-    // Rebase a commit after a commit with multiple children.
-    // "c" has two children "d" and "e", so the rebased commit "f" will inherit the
-    // two children.
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "f", "--after", "c"]);
+    // In this test, the commit with weird ancestry is not rebased (neither directly
+    // nor indirectly).
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "c", "-d", "a"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits onto destination
-    Rebased 2 descendant commits
-    Working copy now at: xznxytkn 8f8c91d3 f | f
-    Parent commit      : kmkuslsw cd86b3e4 c | c
+    Working copy now at: znkkpsqq 7a3bc050 c | c
+    Parent commit      : royxmykx 86a06598 a | a
     Added 0 files, modified 0 files, removed 1 files
     "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  c
+    │ ◉  b
+    ╭─┤
+    ◉ │  a
+    ├─╯
+    ◉  base
+    ◉  notroot
+    ◉
+    "###);
+}
+
+#[test]
+fn test_rebase_revisions_after() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b1", &["a"]);
+    create_commit(&test_env, &repo_path, "b2", &["b1"]);
+    create_commit(&test_env, &repo_path, "b3", &["a"]);
+    create_commit(&test_env, &repo_path, "b4", &["b3"]);
+    create_commit(&test_env, &repo_path, "c", &["b2", "b4"]);
+    create_commit(&test_env, &repo_path, "d", &["c"]);
+    create_commit(&test_env, &repo_path, "e", &["c"]);
+    create_commit(&test_env, &repo_path, "f", &["e"]);
+    // Test the setup
     insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    ◉  e  nkmrtpmo  03ade273
-    │ ◉  d  lylxulpl  8bccbeda
+    @  f  xznxytkn  e4a00798
+    ◉  e  nkmrtpmo  858693f7
+    │ ◉  d  lylxulpl  7d0512e5
     ├─╯
-    @  f  xznxytkn  8f8c91d3
     ◉    c  kmkuslsw  cd86b3e4
     ├─╮
     │ ◉  b4  znkkpsqq  a52a83a4
@@ -1512,26 +2070,23 @@ fn test_rebase_revisions_after() {
     ◉  a  rlvkpnrz  2443ea76
     ◉    zzzzzzzz  00000000
     "###);
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+    let setup_opid = test_env.current_operation_id(&repo_path);
 
-    // Rebase a commit after multiple commits.
+    // Rebasing a commit after its parents should be a no-op.
     let (stdout, stderr) = test_env.jj_cmd_ok(
         &repo_path,
-        &["rebase", "-r", "f", "--after", "e", "--after", "d"],
+        &["rebase", "-r", "c", "--after", "b2", "--after", "b4"],
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Rebased 1 commits onto destination
-    Working copy now at: xznxytkn 7784e5a0 f | f
-    Parent commit      : nkmrtpmo 858693f7 e | e
-    Parent commit      : lylxulpl 7d0512e5 d | d
-    Added 1 files, modified 0 files, removed 0 files
+    Skipped rebase of 4 commits that were already in place
+    Nothing changed.
     "###);
+    assert_eq!(test_env.current_operation_id(&repo_path), setup_opid);
     insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    @    f  xznxytkn  7784e5a0
-    ├─╮
+    @  f  xznxytkn  e4a00798
+    ◉  e  nkmrtpmo  858693f7
     │ ◉  d  lylxulpl  7d0512e5
-    ◉ │  e  nkmrtpmo  858693f7
     ├─╯
     ◉    c  kmkuslsw  cd86b3e4
     ├─╮
@@ -1543,9 +2098,200 @@ fn test_rebase_revisions_after() {
     ◉  a  rlvkpnrz  2443ea76
     ◉    zzzzzzzz  00000000
     "###);
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
 
-    // Rebase two unrelated commits.
+    // Rebasing a commit after itself should be a no-op.
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "c", "--after", "c"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Skipped rebase of 4 commits that were already in place
+    Nothing changed.
+    "###);
+    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
+    @  f  xznxytkn  e4a00798
+    ◉  e  nkmrtpmo  858693f7
+    │ ◉  d  lylxulpl  7d0512e5
+    ├─╯
+    ◉    c  kmkuslsw  cd86b3e4
+    ├─╮
+    │ ◉  b4  znkkpsqq  a52a83a4
+    │ ◉  b3  vruxwmqv  523e6a8b
+    ◉ │  b2  royxmykx  2b8e1148
+    ◉ │  b1  zsuskuln  072d5ae1
+    ├─╯
+    ◉  a  rlvkpnrz  2443ea76
+    ◉    zzzzzzzz  00000000
+    "###);
+
+    // Rebase a commit after another commit. "c" has parents "b2" and "b4", so its
+    // children "d" and "e" should be rebased onto "b2" and "b4" respectively.
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "c", "--after", "e"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Rebased 3 descendant commits
+    Working copy now at: xznxytkn e0e873c8 f | f
+    Parent commit      : kmkuslsw 754793f3 c | c
+    "###);
+    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
+    @  f  xznxytkn  e0e873c8
+    ◉  c  kmkuslsw  754793f3
+    ◉    e  nkmrtpmo  e0d7fb63
+    ├─╮
+    │ │ ◉  d  lylxulpl  5e9cb58d
+    ╭─┬─╯
+    │ ◉  b4  znkkpsqq  a52a83a4
+    │ ◉  b3  vruxwmqv  523e6a8b
+    ◉ │  b2  royxmykx  2b8e1148
+    ◉ │  b1  zsuskuln  072d5ae1
+    ├─╯
+    ◉  a  rlvkpnrz  2443ea76
+    ◉    zzzzzzzz  00000000
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+
+    // Rebase a commit after a leaf commit.
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "e", "--after", "f"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Rebased 1 descendant commits
+    Working copy now at: xznxytkn 9804b742 f | f
+    Parent commit      : kmkuslsw cd86b3e4 c | c
+    Added 0 files, modified 0 files, removed 1 files
+    "###);
+    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
+    ◉  e  nkmrtpmo  76ac6464
+    @  f  xznxytkn  9804b742
+    │ ◉  d  lylxulpl  7d0512e5
+    ├─╯
+    ◉    c  kmkuslsw  cd86b3e4
+    ├─╮
+    │ ◉  b4  znkkpsqq  a52a83a4
+    │ ◉  b3  vruxwmqv  523e6a8b
+    ◉ │  b2  royxmykx  2b8e1148
+    ◉ │  b1  zsuskuln  072d5ae1
+    ├─╯
+    ◉  a  rlvkpnrz  2443ea76
+    ◉    zzzzzzzz  00000000
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+
+    // Rebase a commit after a commit in a branch of a merge commit.
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "f", "--after", "b1"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Rebased 4 descendant commits
+    Working copy now at: xznxytkn 80c27408 f | f
+    Parent commit      : zsuskuln 072d5ae1 b1 | b1
+    Added 0 files, modified 0 files, removed 5 files
+    "###);
+    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
+    ◉  e  nkmrtpmo  cee7a197
+    │ ◉  d  lylxulpl  1eb960ec
+    ├─╯
+    ◉    c  kmkuslsw  305a7803
+    ├─╮
+    │ ◉  b4  znkkpsqq  a52a83a4
+    │ ◉  b3  vruxwmqv  523e6a8b
+    ◉ │  b2  royxmykx  526481b4
+    @ │  f  xznxytkn  80c27408
+    ◉ │  b1  zsuskuln  072d5ae1
+    ├─╯
+    ◉  a  rlvkpnrz  2443ea76
+    ◉    zzzzzzzz  00000000
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+
+    // Rebase a commit after the last commit in a branch of a merge commit. "f"
+    // takes the place of "b2" as the first parent of "c".
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "f", "--after", "b2"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Rebased 3 descendant commits
+    Working copy now at: xznxytkn ebbc24b1 f | f
+    Parent commit      : royxmykx 2b8e1148 b2 | b2
+    Added 0 files, modified 0 files, removed 4 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  e
+    │ ◉  d
+    ├─╯
+    ◉    c
+    ├─╮
+    @ │  f
+    ◉ │  b2
+    ◉ │  b1
+    │ ◉  b4
+    │ ◉  b3
+    ├─╯
+    ◉  a
+    ◉
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+
+    // Rebase a commit after a commit with multiple children.
+    // "c" has two children "d" and "e", so the rebased commit "f" will inherit the
+    // two children.
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "f", "--after", "c"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Rebased 2 descendant commits
+    Working copy now at: xznxytkn 8f8c91d3 f | f
+    Parent commit      : kmkuslsw cd86b3e4 c | c
+    Added 0 files, modified 0 files, removed 1 files
+    "###);
+    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
+    ◉  e  nkmrtpmo  03ade273
+    │ ◉  d  lylxulpl  8bccbeda
+    ├─╯
+    @  f  xznxytkn  8f8c91d3
+    ◉    c  kmkuslsw  cd86b3e4
+    ├─╮
+    │ ◉  b4  znkkpsqq  a52a83a4
+    │ ◉  b3  vruxwmqv  523e6a8b
+    ◉ │  b2  royxmykx  2b8e1148
+    ◉ │  b1  zsuskuln  072d5ae1
+    ├─╯
+    ◉  a  rlvkpnrz  2443ea76
+    ◉    zzzzzzzz  00000000
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+
+    // Rebase a commit after multiple commits.
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-r", "f", "--after", "e", "--after", "d"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Working copy now at: xznxytkn 7784e5a0 f | f
+    Parent commit      : nkmrtpmo 858693f7 e | e
+    Parent commit      : lylxulpl 7d0512e5 d | d
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
+    @    f  xznxytkn  7784e5a0
+    ├─╮
+    │ ◉  d  lylxulpl  7d0512e5
+    ◉ │  e  nkmrtpmo  858693f7
+    ├─╯
+    ◉    c  kmkuslsw  cd86b3e4
+    ├─╮
+    │ ◉  b4  znkkpsqq  a52a83a4
+    │ ◉  b3  vruxwmqv  523e6a8b
+    ◉ │  b2  royxmykx  2b8e1148
+    ◉ │  b1  zsuskuln  072d5ae1
+    ├─╯
+    ◉  a  rlvkpnrz  2443ea76
+    ◉    zzzzzzzz  00000000
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+
+    // Rebase two unrelated commits.
     let (stdout, stderr) = test_env.jj_cmd_ok(
         &repo_path,
         &["rebase", "-r", "d", "-r", "e", "--after", "a"],
@@ -1873,33 +2619,51 @@ fn test_rebase_revisions_before() {
     "###);
     test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
 
-    // Rebase a commit before a merge commit. "c" has two parents "b2" and "b4", so
-    // the rebased commit "f" will have the two commits "b2" and "b4" as its
-    // parents.
+    // Rebase a commit before a merge commit. Only the edge between "c" and its
+    // first parent "b2" is spliced, so "f" is inserted between "b2" and "c",
+    // taking the place of "b2" as the first parent of "c". "c" keeps its other
+    // parent "b4".
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "f", "--before", "c"]);
+    let get_parents = |revision: &str| {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "log",
+                "--no-graph",
+                "-r",
+                revision,
+                "-T",
+                r#"parents.map(|c| c.description().first_line()).join(" ")"#,
+            ],
+        )
+    };
     insta::assert_snapshot!(stdout, @"");
-    insta::assert_snapshot!(stderr, @r###"
-    Rebased 1 commits onto destination
-    Rebased 3 descendant commits
-    Working copy now at: xznxytkn aae1bc10 f | f
-    Parent commit      : royxmykx 2b8e1148 b2 | b2
-    Parent commit      : znkkpsqq a52a83a4 b4 | b4
-    Added 0 files, modified 0 files, removed 2 files
-    "###);
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    ◉  e  nkmrtpmo  0ea67093
-    │ ◉  d  lylxulpl  c079568d
+    insta::with_settings!({filters => vec![(r"xznxytkn [0-9a-f]{8}", "xznxytkn [hash]")]}, {
+        insta::assert_snapshot!(stderr, @r###"
+        Rebased 1 commits onto destination
+        Rebased 3 descendant commits
+        Working copy now at: xznxytkn [hash] f | f
+        Parent commit      : royxmykx 2b8e1148 b2 | b2
+        Added 0 files, modified 0 files, removed 4 files
+        "###);
+    });
+    assert_eq!(get_parents("f"), "b2");
+    assert_eq!(get_parents("c"), "f b4");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["log", "-T", "description.first_line()"]);
+    insta::assert_snapshot!(stdout, @r###"
+    ◉  e
+    │ ◉  d
     ├─╯
-    ◉  c  kmkuslsw  6371742b
-    @    f  xznxytkn  aae1bc10
+    ◉    c
     ├─╮
-    │ ◉  b4  znkkpsqq  a52a83a4
-    │ ◉  b3  vruxwmqv  523e6a8b
-    ◉ │  b2  royxmykx  2b8e1148
-    ◉ │  b1  zsuskuln  072d5ae1
+    @ │  f
+    ◉ │  b2
+    ◉ │  b1
+    │ ◉  b4
+    │ ◉  b3
     ├─╯
-    ◉  a  rlvkpnrz  2443ea76
-    ◉    zzzzzzzz  00000000
+    ◉  a
+    ◉
     "###);
     test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
 
@@ -2033,247 +2797,1628 @@ fn test_rebase_revisions_before() {
     // "e", "b1" should be a parent of "e" after the rebase.
     let (stdout, stderr) = test_env.jj_cmd_ok(
         &repo_path,
-        &["rebase", "-r", "b1", "-r", "e", "--before", "a"],
+        &["rebase", "-r", "b1", "-r", "e", "--before", "a"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 2 commits onto destination
+    Rebased 7 descendant commits
+    Working copy now at: xznxytkn 1c48b514 f | f
+    Parent commit      : kmkuslsw c0fd979a c | c
+    "###);
+    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
+    @  f  xznxytkn  1c48b514
+    │ ◉  d  lylxulpl  4dbbc808
+    ├─╯
+    ◉    c  kmkuslsw  c0fd979a
+    ├─╮
+    │ ◉  b4  znkkpsqq  4d5c61f4
+    │ ◉  b3  vruxwmqv  d5699c24
+    ◉ │  b2  royxmykx  e23ab998
+    ├─╯
+    ◉  a  rlvkpnrz  076f0094
+    ◉  e  nkmrtpmo  20d1f131
+    ◉  b1  zsuskuln  11db739a
+    ◉    zzzzzzzz  00000000
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+
+    // Should error if a loop will be created.
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["rebase", "-r", "e", "--before", "b2", "--before", "c"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Refusing to create a loop: commit 2b8e1148290f would be both an ancestor and a descendant of the rebased commits
+    "###);
+}
+
+#[test]
+fn test_rebase_revisions_after_before() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b1", &["a"]);
+    create_commit(&test_env, &repo_path, "b2", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b1", "b2"]);
+    create_commit(&test_env, &repo_path, "d", &["c"]);
+    create_commit(&test_env, &repo_path, "e", &["c"]);
+    create_commit(&test_env, &repo_path, "f", &["e"]);
+    // Test the setup
+    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
+    @  f  lylxulpl  88f778c5
+    ◉  e  kmkuslsw  48dd9e3f
+    │ ◉  d  znkkpsqq  92438fc9
+    ├─╯
+    ◉    c  vruxwmqv  c41e416e
+    ├─╮
+    │ ◉  b2  royxmykx  903ab0d6
+    ◉ │  b1  zsuskuln  072d5ae1
+    ├─╯
+    ◉  a  rlvkpnrz  2443ea76
+    ◉    zzzzzzzz  00000000
+    "###);
+    let setup_opid = test_env.current_operation_id(&repo_path);
+
+    // Rebase a commit after another commit and before that commit's child to
+    // insert directly between the two commits.
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-r", "d", "--after", "e", "--before", "f"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Rebased 1 descendant commits
+    Working copy now at: lylxulpl fe3d8c30 f | f
+    Parent commit      : znkkpsqq cca70ee1 d | d
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
+    @  f  lylxulpl  fe3d8c30
+    ◉  d  znkkpsqq  cca70ee1
+    ◉  e  kmkuslsw  48dd9e3f
+    ◉    c  vruxwmqv  c41e416e
+    ├─╮
+    │ ◉  b2  royxmykx  903ab0d6
+    ◉ │  b1  zsuskuln  072d5ae1
+    ├─╯
+    ◉  a  rlvkpnrz  2443ea76
+    ◉    zzzzzzzz  00000000
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+
+    // Rebase a commit after another commit and before that commit's descendant to
+    // create a new merge commit.
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-r", "d", "--after", "a", "--before", "f"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Rebased 1 descendant commits
+    Working copy now at: lylxulpl 22f0323c f | f
+    Parent commit      : kmkuslsw 48dd9e3f e | e
+    Parent commit      : znkkpsqq 61388bb6 d | d
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
+    @    f  lylxulpl  22f0323c
+    ├─╮
+    │ ◉  d  znkkpsqq  61388bb6
+    ◉ │  e  kmkuslsw  48dd9e3f
+    ◉ │    c  vruxwmqv  c41e416e
+    ├───╮
+    │ │ ◉  b2  royxmykx  903ab0d6
+    │ ├─╯
+    ◉ │  b1  zsuskuln  072d5ae1
+    ├─╯
+    ◉  a  rlvkpnrz  2443ea76
+    ◉    zzzzzzzz  00000000
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+
+    // "c" has parents "b1" and "b2", so when it is rebased, its children "d" and
+    // "e" should have "b1" and "b2" as parents as well. "c" is then inserted in
+    // between "d" and "e". Since "b1" and "b2" are ancestors of "d", "e" only
+    // has "c" as its parent.
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-r", "c", "--after", "d", "--before", "e"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::with_settings!({filters => vec![
+        (r"lylxulpl [0-9a-f]{8}", "lylxulpl [hash]"),
+        (r"kmkuslsw [0-9a-f]{8}", "kmkuslsw [hash]"),
+    ]}, {
+        insta::assert_snapshot!(stderr, @r###"
+        Rebased 1 commits onto destination
+        Rebased 3 descendant commits
+        Working copy now at: lylxulpl [hash] f | f
+        Parent commit      : kmkuslsw [hash] e | e
+        Added 1 files, modified 0 files, removed 0 files
+        "###);
+    });
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  f
+    ◉  e
+    ◉  c
+    ◉    d
+    ├─╮
+    │ ◉  b2
+    ◉ │  b1
+    ├─╯
+    ◉  a
+    ◉
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+
+    // Rebase multiple commits and preserve their ancestry. Apart from the heads of
+    // the target commits ("d" and "e"), "f" also has commits "b1" and "b2" as
+    // parents since its parents "d" and "e" were in the target set and were
+    // replaced by their closest ancestors outside the target set.
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase", "-r", "c", "-r", "d", "-r", "e", "--after", "a", "--before", "f",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 3 commits onto destination
+    Rebased 1 descendant commits
+    Working copy now at: lylxulpl 868f6c61 f | f
+    Parent commit      : zsuskuln 072d5ae1 b1 | b1
+    Parent commit      : royxmykx 903ab0d6 b2 | b2
+    Parent commit      : znkkpsqq ae6181e6 d | d
+    Parent commit      : kmkuslsw a55a6779 e | e
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
+    @        f  lylxulpl  868f6c61
+    ├─┬─┬─╮
+    │ │ │ ◉  e  kmkuslsw  a55a6779
+    │ │ ◉ │  d  znkkpsqq  ae6181e6
+    │ │ ├─╯
+    │ │ ◉  c  vruxwmqv  22540859
+    │ ◉ │  b2  royxmykx  903ab0d6
+    │ ├─╯
+    ◉ │  b1  zsuskuln  072d5ae1
+    ├─╯
+    ◉  a  rlvkpnrz  2443ea76
+    ◉    zzzzzzzz  00000000
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+
+    // Should error if a loop will be created, listing every commit in the loop.
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["rebase", "-r", "e", "--after", "c", "--before", "a"],
+    );
+    let get_commit_id = |revision: &str| {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "log",
+                "--no-graph",
+                "-r",
+                revision,
+                "-T",
+                "commit_id.short()",
+            ],
+        )
+    };
+    assert_eq!(
+        stderr,
+        format!(
+            "Error: Refusing to create a loop: commits c41e416ee4cf, {}, {}, 2443ea76b0b1 would \
+             be both ancestors and descendants of the rebased commits\n",
+            get_commit_id("b2"),
+            get_commit_id("b1"),
+        )
+    );
+}
+
+#[test]
+fn test_rebase_source_insert_after_before() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    create_commit(&test_env, &repo_path, "d", &["a"]);
+    create_commit(&test_env, &repo_path, "e", &["d"]);
+    create_commit(&test_env, &repo_path, "f", &[]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  f
+    │ ◉  e
+    │ ◉  d
+    │ │ ◉  c
+    │ │ ◉  b
+    │ ├─╯
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+
+    // Insert a subtree after a commit. The children of that commit are rebased
+    // onto the head of the subtree.
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "d", "--after", "b"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 2 commits onto destination
+    Rebased 1 descendant commits
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  c
+    ◉  e
+    ◉  d
+    ◉  b
+    ◉  a
+    │ @  f
+    ├─╯
+    ◉
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // Insert a subtree before a commit
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "d", "--before", "b"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Skipped rebase of 2 commits that were already in place
+    Rebased 2 descendant commits
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  c
+    ◉  b
+    ◉  e
+    ◉  d
+    ◉  a
+    │ @  f
+    ├─╯
+    ◉
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // With -b, the branch is relative to the commits to insert after
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-b", "e", "--after", "c"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 2 commits onto destination
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  e
+    ◉  d
+    ◉  c
+    ◉  b
+    ◉  a
+    │ @  f
+    ├─╯
+    ◉
+    "###);
+}
+
+#[test]
+fn test_rebase_skip_emptied() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "a", "-m", "will become empty"]);
+    test_env.jj_cmd_ok(&repo_path, &["restore", "--from=b"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "already empty"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "also already empty"]);
+
+    // Test the setup
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["log", "-T", "description"]), @r###"
+    @  also already empty
+    ◉  already empty
+    ◉  will become empty
+    │ ◉  b
+    ├─╯
+    ◉  a
+    ◉
+    "###);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-d=b", "--skip-emptied"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 3 commits
+    Working copy now at: yostqsxw 6b74c840 (empty) also already empty
+    Parent commit      : vruxwmqv 48a31526 (empty) already empty
+    "###);
+
+    // The parent commit became empty and was dropped, but the already empty commits
+    // were kept
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["log", "-T", "description"]), @r###"
+    @  also already empty
+    ◉  already empty
+    ◉  b
+    ◉  a
+    ◉
+    "###);
+}
+
+#[test]
+fn test_rebase_revisions_skip_emptied() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "a", "-m", "will become empty"]);
+    test_env.jj_cmd_ok(&repo_path, &["restore", "--from=b"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "child"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m", "e"]);
+
+    // Test the setup
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["log", "-T", "description"]), @r###"
+    @  e
+    │ ◉  child
+    │ ◉  will become empty
+    │ │ ◉  b
+    │ ├─╯
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+
+    // The rebased revision became empty and was dropped, but its descendant was
+    // kept even though it's empty
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "-r",
+            "description(become)",
+            "-d=b",
+            "--skip-emptied",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 descendant commits
+    Abandoned 1 newly emptied commits
+    "###);
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["log", "-T", "description"]), @r###"
+    ◉  child
+    │ ◉  b
+    ├─╯
+    ◉  a
+    │ @  e
+    ├─╯
+    ◉
+    "###);
+
+    // The deprecated --skip-empty works with -r too
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "-r",
+            "description(become)",
+            "-d=b",
+            "--skip-empty",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Warning: --skip-empty is deprecated, use --empty=drop-newly-empty instead.
+    Rebased 1 descendant commits
+    Abandoned 1 newly emptied commits
+    "###);
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["log", "-T", "description"]), @r###"
+    ◉  child
+    │ ◉  b
+    ├─╯
+    ◉  a
+    │ @  e
+    ├─╯
+    ◉
+    "###);
+}
+
+#[test]
+fn test_rebase_skip_emptied_reports_moved_branch() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "a", "-m", "will become empty"]);
+    test_env.jj_cmd_ok(&repo_path, &["restore", "--from=b"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "x"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m", "e"]);
+    let get_commit_id = |revision: &str| {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "log",
+                "--no-graph",
+                "-r",
+                revision,
+                "-T",
+                "commit_id.short()",
+            ],
+        )
+    };
+    let b_id = get_commit_id("b");
+    let x_id = get_commit_id("x");
+
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @
+    │ ◉  x
+    │ │ ◉  b
+    │ ├─╯
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+
+    // The branch on the abandoned commit is moved to its new parent
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["rebase", "-s=x", "-d=b", "--skip-emptied"]);
+    assert_eq!(stdout, "");
+    assert_eq!(
+        stderr,
+        format!(
+            "Rebased 1 commits\n\
+             Branch x moved from abandoned commit {x_id} to {b_id}\n"
+        )
+    );
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @
+    │ ◉  b x
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // Same with -r
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["rebase", "-r=x", "-d=b", "--skip-emptied"]);
+    assert_eq!(stdout, "");
+    assert_eq!(
+        stderr,
+        format!(
+            "Abandoned 1 newly emptied commits\n\
+             Branch x moved from abandoned commit {x_id} to {b_id}\n"
+        )
+    );
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @
+    │ ◉  b x
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+}
+
+#[test]
+fn test_rebase_skip_emptied_descendants() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "a", "-m", "will become empty"]);
+    test_env.jj_cmd_ok(&repo_path, &["restore", "--from=b"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "child"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m", "e"]);
+
+    // Test the setup
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["log", "-T", "description"]), @r###"
+    @  e
+    │ ◉  child
+    │ ◉  will become empty
+    │ │ ◉  b
+    │ ├─╯
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+
+    // Descendants are kept by default
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-s", "b", "--before", "description(become)"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Skipped rebase of 1 commits that were already in place
+    Rebased 2 descendant commits
+    "###);
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["log", "-T", "description"]), @r###"
+    ◉  child
+    ◉  will become empty
+    ◉  b
+    ◉  a
+    │ @  e
+    ├─╯
+    ◉
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // The descendant which became empty is abandoned, as with a plain `-s`
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "-s",
+            "b",
+            "--before",
+            "description(become)",
+            "--skip-emptied",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Skipped rebase of 1 commits that were already in place
+    Rebased 1 descendant commits
+    Abandoned 1 newly emptied descendant commits
+    "###);
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["log", "-T", "description"]), @r###"
+    ◉  child
+    ◉  b
+    ◉  a
+    │ @  e
+    ├─╯
+    ◉
+    "###);
+}
+
+#[test]
+fn test_rebase_empty_mode() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "a", "-m", "will become empty"]);
+    test_env.jj_cmd_ok(&repo_path, &["restore", "--from=b"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "already empty"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m", "e"]);
+
+    // Test the setup
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["log", "-T", "description"]), @r###"
+    @  e
+    │ ◉  already empty
+    │ ◉  will become empty
+    │ │ ◉  b
+    │ ├─╯
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+
+    // Commits which were already empty are dropped too
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "-s",
+            "description(become)",
+            "-d=b",
+            "--empty=drop",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 2 commits
+    "###);
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["log", "-T", "description"]), @r###"
+    @  e
+    │ ◉  b
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // The deprecated --skip-empty is the same as --empty=drop-newly-empty
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "-s",
+            "description(become)",
+            "-d=b",
+            "--skip-empty",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Warning: --skip-empty is deprecated, use --empty=drop-newly-empty instead.
+    Rebased 2 commits
+    "###);
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["log", "-T", "description"]), @r###"
+    @  e
+    │ ◉  already empty
+    │ ◉  b
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+}
+
+#[test]
+fn test_rebase_keep_author_timestamp() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let get_author_timestamp = || {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &["log", "--no-graph", "-r", "@", "-T", "author.timestamp()"],
+        )
+    };
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    // The working-copy commit is discardable, so its author timestamp is reset
+    // when it's rewritten
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()"]);
+    let author_timestamp = get_author_timestamp();
+
+    test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "@", "-d", "a"]);
+    assert_ne!(get_author_timestamp(), author_timestamp);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-r", "@", "-d", "a", "--keep-author-timestamp"],
+    );
+    assert_eq!(get_author_timestamp(), author_timestamp);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-s", "@", "-d", "a", "--keep-author-timestamp"],
+    );
+    assert_eq!(get_author_timestamp(), author_timestamp);
+}
+
+#[test]
+fn test_rebase_confirm_threshold() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("rebase.confirm-threshold = 1");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    create_commit(&test_env, &repo_path, "d", &[]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  d
+    │ ◉  c
+    │ ◉  b
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+
+    // Declining aborts the rebase
+    let assert = test_env
+        .jj_cmd_stdin(&repo_path, &["rebase", "-s", "b", "-d", "d"], "n\n")
+        .assert()
+        .code(1);
+    let stdout = test_env.normalize_output(&get_stdout_string(&assert));
+    let stderr = test_env.normalize_output(&get_stderr_string(&assert));
+    insta::assert_snapshot!(stdout, @"This will rewrite 2 commits. Continue? (yN): ");
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Rebase aborted
+    "###);
+
+    // So does EOF, for -r too
+    let assert = test_env
+        .jj_cmd_stdin(&repo_path, &["rebase", "-r", "b", "-d", "d"], "")
+        .assert()
+        .code(1);
+    let stdout = test_env.normalize_output(&get_stdout_string(&assert));
+    let stderr = test_env.normalize_output(&get_stderr_string(&assert));
+    insta::assert_snapshot!(stdout, @"This will rewrite 2 commits. Continue? (yN): ");
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Rebase aborted
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  d
+    │ ◉  c
+    │ ◉  b
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+
+    // Rebasing no more commits than the threshold doesn't ask
+    let (stdout, stderr) =
+        test_env.jj_cmd_stdin_ok(&repo_path, &["rebase", "-s", "c", "-d", "d"], "");
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // Neither does --yes
+    let (stdout, stderr) =
+        test_env.jj_cmd_stdin_ok(&repo_path, &["rebase", "-s", "b", "-d", "d", "--yes"], "");
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 2 commits
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // Accepting rebases the commits
+    let (stdout, stderr) =
+        test_env.jj_cmd_stdin_ok(&repo_path, &["rebase", "-s", "b", "-d", "d"], "y\n");
+    insta::assert_snapshot!(stdout, @"This will rewrite 2 commits. Continue? (yN): ");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 2 commits
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  c
+    ◉  b
+    @  d
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+}
+
+#[test]
+fn test_rebase_show_mapping() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let get_commit_id = |revision: &str| {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "log",
+                "--no-graph",
+                "-r",
+                revision,
+                "-T",
+                "commit_id.short()",
+            ],
+        )
+    };
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "e", &[]);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-s", "b", "-d", "root()", "--show-mapping"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits
+    "###);
+    assert_eq!(stdout, format!("1394f625cbbd -> {}\n", get_commit_id("b")));
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // The commits are listed parents first
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-r", "b", "--before", "a", "--show-mapping"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Rebased 1 descendant commits
+    "###);
+    assert_eq!(
+        stdout,
+        format!(
+            "2443ea76b0b1 -> {}\n1394f625cbbd -> {}\n",
+            get_commit_id("a"),
+            get_commit_id("b")
+        )
+    );
+}
+
+#[test]
+fn test_rebase_show_result() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"templates.log = "branches""#);
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    create_commit(&test_env, &repo_path, "d", &[]);
+
+    // The rebased commits are shown along with their new parents
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-s", "b", "-d", "d", "--show-result"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    ◉  c
+    ◉  b
+    @  d
+    │
+    ~
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 2 commits
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-r", "c", "-d", "a", "--show-result"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    ◉  c
+    ◉  a
+    │
+    ~
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    "###);
+}
+
+#[test]
+fn test_rebase_interactive() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let edit_script = test_env.set_up_fake_editor();
+    let get_template_output = |revision: &str, template: &str| {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &["log", "--no-graph", "-r", revision, "-T", template],
+        )
+    };
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    create_commit(&test_env, &repo_path, "d", &[]);
+    let change_b = get_template_output("b", "change_id.short()");
+    let change_c = get_template_output("c", "change_id.short()");
+    let change_d = get_template_output("d", "change_id.short()");
+
+    // The revisions are listed parents first with their current parents. Swap
+    // them by editing their parents.
+    std::fs::write(
+        &edit_script,
+        [
+            "dump editor0",
+            &format!("write\n{change_c}: a\n{change_b}: c\n"),
+        ]
+        .join("\0"),
+    )
+    .unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "b", "-r", "c", "-i"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 2 commits onto destination
+    "###);
+    let editor_lines = std::fs::read_to_string(test_env.env_root().join("editor0"))
+        .unwrap()
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with("JJ: "))
+        .map(|line| line.to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        editor_lines,
+        [
+            format!("{change_b}: 2443ea76b0b1"),
+            format!("{change_c}: 1394f625cbbd"),
+        ]
+    );
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  b
+    ◉  c
+    ◉  a
+    │ @  d
+    ├─╯
+    ◉
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // Removing a line leaves the parents unchanged
+    std::fs::write(&edit_script, format!("write\n{change_c}: d\n")).unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "b", "-r", "c", "-i"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Skipped rebase of 1 commits that were already in place
+    Rebased 1 commits onto destination
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  c
+    @  d
+    │ ◉  b
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // Parents must not form a loop
+    std::fs::write(
+        &edit_script,
+        format!("write\n{change_b}: c\n{change_c}: b\n"),
+    )
+    .unwrap();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-r", "b", "-r", "c", "-i"]);
+    assert_eq!(
+        stderr,
+        format!(
+            "Error: Refusing to create a loop: commits {}, {} would be both ancestors and \
+             descendants of the rebased commits\n",
+            get_template_output("c", "commit_id.short()"),
+            get_template_output("b", "commit_id.short()"),
+        )
+    );
+
+    // Only the rebased revisions can be listed
+    std::fs::write(&edit_script, format!("write\n{change_d}: a\n")).unwrap();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-r", "b", "-r", "c", "-i"]);
+    assert_eq!(
+        stderr,
+        format!("Error: Revision {change_d} is not one of the revisions being rebased\n")
+    );
+
+    // -i doesn't take a destination
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["rebase", "-r", "b", "-i", "-d", "a"]);
+    insta::assert_snapshot!(stderr, @r###"
+    error: the argument '--interactive' cannot be used with '--destination <DESTINATION>'
+
+    Usage: jj rebase --revisions <REVISIONS> <--destination <DESTINATION>|--insert-after <INSERT_AFTER>|--insert-before <INSERT_BEFORE>|--auto-destination|--interactive>
+
+    For more information, try '--help'.
+    "###);
+}
+
+#[test]
+fn test_rebase_move_descendants() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "j", &[]);
+    create_commit(&test_env, &repo_path, "k", &["j"]);
+    create_commit(&test_env, &repo_path, "l", &["k"]);
+    create_commit(&test_env, &repo_path, "m", &["j"]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  m
+    │ ◉  l
+    │ ◉  k
+    ├─╯
+    ◉  j
+    ◉
+    "###);
+
+    // By default, "l" fills the hole left behind by "k"
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "k", "-d", "m"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Rebased 1 descendant commits
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  l
+    │ ◉  k
+    │ @  m
+    ├─╯
+    ◉  j
+    ◉
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // With --move-descendants, "l" stays attached to "k"
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-r", "k", "-d", "m", "--move-descendants"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Rebased 1 descendant commits
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  l
+    ◉  k
+    @  m
+    ◉  j
+    ◉
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // The descendants can't be moved onto themselves
+    let get_commit_id = |revision: &str| {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "log",
+                "--no-graph",
+                "-r",
+                revision,
+                "-T",
+                "commit_id.short()",
+            ],
+        )
+    };
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["rebase", "-r", "k", "-d", "l", "--move-descendants"],
+    );
+    assert_eq!(
+        stderr,
+        format!(
+            "Error: Cannot rebase {} onto its descendant {} while moving its descendants\nHint: \
+             Rebase without `--move-descendants` to leave the descendants in place.\n",
+            get_commit_id("k"),
+            get_commit_id("l")
+        )
+    );
+}
+
+#[test]
+fn test_rebase_rewritten_remote_branches() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    // Set up remote
+    let git_repo_path = test_env.env_root().join("git-repo");
+    git2::Repository::init_bare(git_repo_path).unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["git", "remote", "add", "origin", "../git-repo"],
+    );
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &[]);
+    test_env.jj_cmd_ok(&repo_path, &["git", "push", "-b=a", "-b=b"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "untrack", "b@origin"]);
+
+    // Both tracked and untracked remote branches are reported
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "a", "-d", "c"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 2 commits
+    Warning: The tracked remote branch a@origin points to a rewritten commit
+    Warning: The untracked remote branch b@origin points to a rewritten commit
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // Only the remote branches pointing to rewritten commits are reported
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "b", "-d", "c"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 1 commits onto destination
+    Warning: The untracked remote branch b@origin points to a rewritten commit
+    "###);
+}
+
+#[test]
+fn test_rebase_explain() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"templates.commit_summary = "description.first_line()""#);
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+
+    // The new parents of "c" and of the commit it is inserted before are printed
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "-r",
+            "c",
+            "--after",
+            "a",
+            "--before",
+            "b",
+            "--explain",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Commit c
+      Old parent: b
+      New parent: a
+    Commit b
+      Old parent: a
+      New parent: c
+    "###);
+    insta::assert_snapshot!(stderr, @"");
+    // Nothing was rebased
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  c
+    ◉  b
+    ◉  a
+    ◉
+    "###);
+
+    // A commit which is already in place has no new parents to print, and it
+    // isn't reported as skipped
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "c", "-d", "b", "--explain"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @"");
+
+    let stderr =
+        test_env.jj_cmd_failure(&repo_path, &["rebase", "-s", "b", "-d", "a", "--explain"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: --explain only works with -r, --insert-after, or --insert-before
+    "###);
+}
+
+#[test]
+fn test_rebase_skip() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    create_commit(&test_env, &repo_path, "d", &["c"]);
+    create_commit(&test_env, &repo_path, "e", &[]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  e
+    │ ◉  d
+    │ ◉  c
+    │ ◉  b
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+
+    // The skipped commit stays on its nearest unmoved ancestor, and the moved
+    // commit above it is rebased onto its nearest moved ancestor
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "b", "-d", "e", "--skip", "c"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 2 commits onto destination
+    Rebased 1 descendant commits
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  d
+    ◉  b
+    @  e
+    │ ◉  c
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // A skipped commit can't be the destination of the commits it descends from
+    let stderr =
+        test_env.jj_cmd_failure(&repo_path, &["rebase", "-s", "a", "-d", "b", "--skip", "b"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot rebase 2443ea76b0b1 onto descendant 1394f625cbbd
+    Hint: The destination is part of the commits being rebased. Use `jj rebase -r` to rebase a revision without its descendants.
+    "###);
+
+    // With -b, the skipped commits are excluded from the branch
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["rebase", "-b", "d", "-d", "e", "--skip", "a"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 3 commits onto destination
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  d
+    ◉  c
+    ◉  b
+    @  e
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+}
+
+#[test]
+fn test_rebase_skip_merge() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["a"]);
+    create_commit(&test_env, &repo_path, "d", &["b", "c"]);
+    create_commit(&test_env, &repo_path, "e", &["d"]);
+    create_commit(&test_env, &repo_path, "f", &[]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  f
+    │ ◉  e
+    │ ◉    d
+    │ ├─╮
+    │ │ ◉  c
+    │ ◉ │  b
+    │ ├─╯
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+
+    // The skipped merge is rebased onto the nearest unmoved ancestor of its
+    // parents, and its child becomes a merge of the moved parents
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["rebase", "-s", "a", "-d", "f", "--skip", "d"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 4 commits onto destination
+    Rebased 1 descendant commits
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉    e
+    ├─╮
+    │ ◉  c
+    ◉ │  b
+    ├─╯
+    ◉  a
+    @  f
+    │ ◉  d
+    ├─╯
+    ◉
+    "###);
+}
+
+#[test]
+fn test_rebase_fold_trivial() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let edit_script = test_env.set_up_fake_editor();
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &[]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    create_commit(&test_env, &repo_path, "d", &["c"]);
+    create_commit(&test_env, &repo_path, "e", &[]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  e
+    │ ◉  d
+    │ ◉  c
+    │ ◉  b
+    ├─╯
+    │ ◉  a
+    ├─╯
+    ◉
+    "###);
+
+    // Each commit touches a single file. "b" is not folded since its parent was
+    // not rebased, and "d" is not folded since its parent "c" was folded.
+    std::fs::write(&edit_script, "dump editor0").unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-s", "b", "-d", "a", "--fold-trivial", "1"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 3 commits
+    Folded 1 trivial commits into their parents
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  d
+    ◉  b c
+    ◉  a
+    │ @  e
+    ├─╯
+    ◉
+    "###);
+    // Both "b" and "c" have a description, so they are combined in the editor
+    insta::assert_snapshot!(
+        std::fs::read_to_string(test_env.env_root().join("editor0")).unwrap(), @r###"
+    JJ: Enter a description for the combined commit.
+    JJ: Description from the destination commit:
+    b
+
+    JJ: Description from source commit:
+    c
+
+    JJ: Lines starting with "JJ: " (like this one) will be removed.
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r", "b", "-T", "description"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    b
+
+    c
+    "###);
+
+    // Nothing is folded if the threshold is too low
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-s", "b", "-d", "a", "--fold-trivial", "0"],
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Rebased 2 commits onto destination
-    Rebased 7 descendant commits
-    Working copy now at: xznxytkn 1c48b514 f | f
-    Parent commit      : kmkuslsw c0fd979a c | c
+    Rebased 3 commits
     "###);
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    @  f  xznxytkn  1c48b514
-    │ ◉  d  lylxulpl  4dbbc808
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  d
+    ◉  c
+    ◉  b
+    ◉  a
+    │ @  e
     ├─╯
-    ◉    c  kmkuslsw  c0fd979a
-    ├─╮
-    │ ◉  b4  znkkpsqq  4d5c61f4
-    │ ◉  b3  vruxwmqv  d5699c24
-    ◉ │  b2  royxmykx  e23ab998
+    ◉
+    "###);
+}
+
+#[test]
+fn test_rebase_no_update_branches() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &[]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    create_commit(&test_env, &repo_path, "e", &[]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  e
+    │ ◉  c
+    │ ◉  b
     ├─╯
-    ◉  a  rlvkpnrz  076f0094
-    ◉  e  nkmrtpmo  20d1f131
-    ◉  b1  zsuskuln  11db739a
-    ◉    zzzzzzzz  00000000
+    │ ◉  a
+    ├─╯
+    ◉
     "###);
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
 
-    // Should error if a loop will be created.
-    let stderr = test_env.jj_cmd_failure(
+    // The branches stay on the hidden original commits
+    let (stdout, stderr) = test_env.jj_cmd_ok(
         &repo_path,
-        &["rebase", "-r", "e", "--before", "b2", "--before", "c"],
+        &["rebase", "-s", "b", "-d", "a", "--no-update-branches"],
     );
+    insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Error: Refusing to create a loop: commit 2b8e1148290f would be both an ancestor and a descendant of the rebased commits
+    Rebased 2 commits
+    Warning: Left 2 branches pointing to their old commits, which are now hidden
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉
+    ◉
+    ◉  a
+    │ @  e
+    ├─╯
+    ◉
     "###);
 }
 
 #[test]
-fn test_rebase_revisions_after_before() {
+fn test_rebase_message_template() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
+    let log_template = r#"separate(" ", branches, description.first_line())"#;
 
     create_commit(&test_env, &repo_path, "a", &[]);
-    create_commit(&test_env, &repo_path, "b1", &["a"]);
-    create_commit(&test_env, &repo_path, "b2", &["a"]);
-    create_commit(&test_env, &repo_path, "c", &["b1", "b2"]);
-    create_commit(&test_env, &repo_path, "d", &["c"]);
-    create_commit(&test_env, &repo_path, "e", &["c"]);
-    create_commit(&test_env, &repo_path, "f", &["e"]);
-    // Test the setup
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    @  f  lylxulpl  88f778c5
-    ◉  e  kmkuslsw  48dd9e3f
-    │ ◉  d  znkkpsqq  92438fc9
-    ├─╯
-    ◉    c  vruxwmqv  c41e416e
-    ├─╮
-    │ ◉  b2  royxmykx  903ab0d6
-    ◉ │  b1  zsuskuln  072d5ae1
-    ├─╯
-    ◉  a  rlvkpnrz  2443ea76
-    ◉    zzzzzzzz  00000000
-    "###);
-    let setup_opid = test_env.current_operation_id(&repo_path);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "e", &[]);
 
-    // Rebase a commit after another commit and before that commit's child to
-    // insert directly between the two commits.
+    // The description is rewritten even though the commit is already in place
     let (stdout, stderr) = test_env.jj_cmd_ok(
         &repo_path,
-        &["rebase", "-r", "d", "--after", "e", "--before", "f"],
+        &[
+            "rebase",
+            "-r",
+            "b",
+            "-d",
+            "a",
+            "--message-template",
+            r#""PROJ-1: " ++ description"#,
+        ],
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits onto destination
-    Rebased 1 descendant commits
-    Working copy now at: lylxulpl fe3d8c30 f | f
-    Parent commit      : znkkpsqq cca70ee1 d | d
-    Added 1 files, modified 0 files, removed 0 files
     "###);
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    @  f  lylxulpl  fe3d8c30
-    ◉  d  znkkpsqq  cca70ee1
-    ◉  e  kmkuslsw  48dd9e3f
-    ◉    c  vruxwmqv  c41e416e
-    ├─╮
-    │ ◉  b2  royxmykx  903ab0d6
-    ◉ │  b1  zsuskuln  072d5ae1
+    let stdout = test_env.jj_cmd_success(&repo_path, &["log", "-T", log_template]);
+    insta::assert_snapshot!(stdout, @r###"
+    @  e e
+    │ ◉  b PROJ-1: b
+    │ ◉  a a
     ├─╯
-    ◉  a  rlvkpnrz  2443ea76
-    ◉    zzzzzzzz  00000000
+    ◉
     "###);
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
 
-    // Rebase a commit after another commit and before that commit's descendant to
-    // create a new merge commit.
+    // Strip the prefix again while moving the commit
     let (stdout, stderr) = test_env.jj_cmd_ok(
         &repo_path,
-        &["rebase", "-r", "d", "--after", "a", "--before", "f"],
+        &[
+            "rebase",
+            "-r",
+            "b",
+            "-d",
+            "root()",
+            "--message-template",
+            r#"description.remove_prefix("PROJ-1: ")"#,
+        ],
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits onto destination
-    Rebased 1 descendant commits
-    Working copy now at: lylxulpl 22f0323c f | f
-    Parent commit      : kmkuslsw 48dd9e3f e | e
-    Parent commit      : znkkpsqq 61388bb6 d | d
-    Added 1 files, modified 0 files, removed 0 files
     "###);
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    @    f  lylxulpl  22f0323c
-    ├─╮
-    │ ◉  d  znkkpsqq  61388bb6
-    ◉ │  e  kmkuslsw  48dd9e3f
-    ◉ │    c  vruxwmqv  c41e416e
-    ├───╮
-    │ │ ◉  b2  royxmykx  903ab0d6
-    │ ├─╯
-    ◉ │  b1  zsuskuln  072d5ae1
+    let stdout = test_env.jj_cmd_success(&repo_path, &["log", "-T", log_template]);
+    insta::assert_snapshot!(stdout, @r###"
+    ◉  b b
+    │ @  e e
     ├─╯
-    ◉  a  rlvkpnrz  2443ea76
-    ◉    zzzzzzzz  00000000
+    │ ◉  a a
+    ├─╯
+    ◉
     "###);
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
+}
+
+#[test]
+fn test_rebase_split_paths() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &[]);
+    std::fs::write(repo_path.join("other"), "other\n").unwrap();
+    create_commit(&test_env, &repo_path, "e", &[]);
 
-    // "c" has parents "b1" and "b2", so when it is rebased, its children "d" and
-    // "e" should have "b1" and "b2" as parents as well. "c" is then inserted in
-    // between "d" and "e", making "e" a merge commit with 3 parents "b1", "b2",
-    // and "c".
     let (stdout, stderr) = test_env.jj_cmd_ok(
         &repo_path,
-        &["rebase", "-r", "c", "--after", "d", "--before", "e"],
+        &["rebase", "-r", "b", "-d", "a", "--split-paths", "b"],
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits onto destination
-    Rebased 3 descendant commits
-    Working copy now at: lylxulpl e37682c5 f | f
-    Parent commit      : kmkuslsw 9bbc9e53 e | e
-    Added 1 files, modified 0 files, removed 0 files
+    Split 1 commits at the given paths
     "###);
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    @  f  lylxulpl  e37682c5
-    ◉      e  kmkuslsw  9bbc9e53
-    ├─┬─╮
-    │ │ ◉  c  vruxwmqv  e11c7c95
-    │ │ ◉  d  znkkpsqq  37869bd5
-    ╭─┬─╯
-    │ ◉  b2  royxmykx  903ab0d6
-    ◉ │  b1  zsuskuln  072d5ae1
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  b
+    ◉
+    ◉  a
+    │ @  e
     ├─╯
-    ◉  a  rlvkpnrz  2443ea76
-    ◉    zzzzzzzz  00000000
+    ◉
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "-r", "b-"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A b
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "-r", "b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A other
     "###);
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
 
-    // Rebase multiple commits and preserve their ancestry. Apart from the heads of
-    // the target commits ("d" and "e"), "f" also has commits "b1" and "b2" as
-    // parents since its parents "d" and "e" were in the target set and were
-    // replaced by their closest ancestors outside the target set.
+    // The commit is not split if one side would be empty
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
     let (stdout, stderr) = test_env.jj_cmd_ok(
         &repo_path,
         &[
-            "rebase", "-r", "c", "-r", "d", "-r", "e", "--after", "a", "--before", "f",
+            "rebase",
+            "-r",
+            "b",
+            "-d",
+            "a",
+            "--split-paths",
+            "nonexistent",
         ],
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Rebased 3 commits onto destination
-    Rebased 1 descendant commits
-    Working copy now at: lylxulpl 868f6c61 f | f
-    Parent commit      : zsuskuln 072d5ae1 b1 | b1
-    Parent commit      : royxmykx 903ab0d6 b2 | b2
-    Parent commit      : znkkpsqq ae6181e6 d | d
-    Parent commit      : kmkuslsw a55a6779 e | e
-    Added 1 files, modified 0 files, removed 0 files
+    Rebased 1 commits onto destination
     "###);
-    insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
-    @        f  lylxulpl  868f6c61
-    ├─┬─┬─╮
-    │ │ │ ◉  e  kmkuslsw  a55a6779
-    │ │ ◉ │  d  znkkpsqq  ae6181e6
-    │ │ ├─╯
-    │ │ ◉  c  vruxwmqv  22540859
-    │ ◉ │  b2  royxmykx  903ab0d6
-    │ ├─╯
-    ◉ │  b1  zsuskuln  072d5ae1
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  b
+    ◉  a
+    │ @  e
     ├─╯
-    ◉  a  rlvkpnrz  2443ea76
-    ◉    zzzzzzzz  00000000
+    ◉
     "###);
-    test_env.jj_cmd_ok(&repo_path, &["op", "restore", &setup_opid]);
 
-    // Should error if a loop will be created.
-    let stderr = test_env.jj_cmd_failure(
+    // Revisions which are abandoned are not split
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m", "dup"]);
+    std::fs::write(repo_path.join("b"), "b\n").unwrap();
+    std::fs::write(repo_path.join("other"), "other\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["edit", "e"]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(
         &repo_path,
-        &["rebase", "-r", "e", "--after", "c", "--before", "a"],
+        &[
+            "rebase",
+            "-r",
+            "description(dup)",
+            "-d",
+            "b",
+            "--empty=drop-newly-empty",
+            "--split-paths",
+            "b",
+        ],
     );
+    insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Error: Refusing to create a loop: commit c41e416ee4cf would be both an ancestor and a descendant of the rebased commits
+    Abandoned 1 newly emptied commits
     "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r",
+            "description(dup)",
+            "-T",
+            "description",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"");
 }
 
 #[test]
-fn test_rebase_skip_emptied() {
+fn test_rebase_atomic_steps() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
 
     create_commit(&test_env, &repo_path, "a", &[]);
-    create_commit(&test_env, &repo_path, "b", &["a"]);
-    test_env.jj_cmd_ok(&repo_path, &["new", "a", "-m", "will become empty"]);
-    test_env.jj_cmd_ok(&repo_path, &["restore", "--from=b"]);
-    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "already empty"]);
-    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "also already empty"]);
-
-    // Test the setup
-    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["log", "-T", "description"]), @r###"
-    @  also already empty
-    ◉  already empty
-    ◉  will become empty
-    │ ◉  b
-    ├─╯
-    ◉  a
-    ◉
-    "###);
+    create_commit(&test_env, &repo_path, "b", &[]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    create_commit(&test_env, &repo_path, "e", &[]);
 
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-d=b", "--skip-emptied"]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-s", "b", "-d", "a", "--atomic-steps"],
+    );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Rebased 3 commits
-    Working copy now at: yostqsxw 6b74c840 (empty) also already empty
-    Parent commit      : vruxwmqv 48a31526 (empty) already empty
+    Rebased 2 commits
+    Recorded 2 operations
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  c
+    ◉  b
+    ◉  a
+    │ @  e
+    ├─╯
+    ◉
     "###);
 
-    // The parent commit became empty and was dropped, but the already empty commits
-    // were kept
-    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["log", "-T", "description"]), @r###"
-    @  also already empty
-    ◉  already empty
+    // Undoing the last step leaves "c" on the original "b"
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
     ◉  b
     ◉  a
+    │ @  e
+    ├─╯
+    │ ◉  c
+    │ ◉
+    ├─╯
     ◉
     "###);
 }
@@ -2311,6 +4456,7 @@ fn test_rebase_skip_if_on_destination() {
     // Skip rebase with -b
     insta::assert_snapshot!(stderr, @r###"
     Skipped rebase of 2 commits that were already in place
+    Nothing changed.
     "###);
     insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
     @  f  lylxulpl  88f778c5
@@ -2332,6 +4478,7 @@ fn test_rebase_skip_if_on_destination() {
     // Skip rebase with -s
     insta::assert_snapshot!(stderr, @r###"
     Skipped rebase of 1 commits that were already in place
+    Nothing changed.
     "###);
     insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
     @  f  lylxulpl  88f778c5
@@ -2352,7 +4499,6 @@ fn test_rebase_skip_if_on_destination() {
     // Skip rebase with -r since commit has no children
     insta::assert_snapshot!(stderr, @r###"
     Skipped rebase of 1 commits that were already in place
-    Nothing changed.
     "###);
     insta::assert_snapshot!(get_long_log_output(&test_env, &repo_path), @r###"
     @  f  lylxulpl  88f778c5
@@ -2392,6 +4538,13 @@ fn test_rebase_skip_if_on_destination() {
     ◉  a  rlvkpnrz  2443ea76
     ◉    zzzzzzzz  00000000
     "###);
+
+    // Skip rebase of the working-copy commit onto its own parent
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "@", "-d", "@-"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Skipped rebase of 1 commits that were already in place
+    "###);
 }
 
 fn get_long_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
@@ -32,6 +32,7 @@ fn test_report_conflicts() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 3 commits
+    3 commits now have conflicts
     New conflicts appeared in these commits:
       kkmpptxz 64bdec0c (conflict) C
       rlvkpnrz 10a5fd45 (conflict) B
@@ -66,6 +67,7 @@ fn test_report_conflicts() {
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits onto destination
     Rebased 2 descendant commits
+    3 commits now have conflicts
     New conflicts appeared in these commits:
       kkmpptxz 17c72220 (conflict) C
       rlvkpnrz eb93a73d (conflict) B
@@ -124,6 +126,7 @@ fn test_report_conflicts_with_divergent_commits() {
     insta::assert_snapshot!(stderr, @r###"
     Concurrent modification detected, resolving automatically.
     Rebased 3 commits
+    3 commits now have conflicts
     New conflicts appeared in these commits:
       zsuskuln?? b535189c (conflict) C3
       zsuskuln?? 97ce1783 (conflict) C2
@@ -159,6 +162,7 @@ fn test_report_conflicts_with_divergent_commits() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits
+    1 commits now have conflicts
     New conflicts appeared in these commits:
       zsuskuln?? b15416ac (conflict) C2
     To resolve the conflicts, start by updating to it:
@@ -178,6 +182,7 @@ fn test_report_conflicts_with_divergent_commits() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Rebased 1 commits
+    1 commits now have conflicts
     New conflicts appeared in these commits:
       zsuskuln?? 8cc7fde6 (conflict) C3
     To resolve the conflicts, start by updating to it:
@@ -18,18 +18,22 @@ use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use futures::StreamExt;
+use indexmap::{IndexMap, IndexSet};
 use itertools::Itertools;
 use pollster::FutureExt;
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use tracing::instrument;
 
 use crate::backend::{BackendError, BackendResult, CommitId, MergedTreeId};
-use crate::commit::Commit;
+use crate::commit::{Commit, CommitIteratorExt};
 use crate::commit_builder::CommitBuilder;
+use crate::dag_walk;
 use crate::index::Index;
 use crate::matchers::{Matcher, Visit};
 use crate::merged_tree::{MergedTree, MergedTreeBuilder};
 use crate::repo::{MutableRepo, Repo};
 use crate::repo_path::RepoPath;
+use crate::revset::{RevsetEvaluationError, RevsetExpression, RevsetIteratorExt};
 use crate::settings::UserSettings;
 use crate::store::Store;
 
@@ -103,6 +107,46 @@ pub fn rebase_commit(
     builder.write()
 }
 
+/// Computes the tree of `old_commit` rebased onto `new_parents`. Also returns
+/// whether `old_commit` was empty, which is only meaningful if the parents
+/// changed.
+///
+/// Doesn't modify the repo, so it can be called for several commits at once.
+fn rebase_tree(
+    store: &Arc<Store>,
+    index: &dyn Index,
+    old_commit: &Commit,
+    new_parents: &[Commit],
+) -> BackendResult<(bool, MergedTreeId)> {
+    let old_parents: Vec<_> = old_commit.parents().try_collect()?;
+    let old_parent_trees = old_parents
+        .iter()
+        .map(|parent| parent.tree_id().clone())
+        .collect_vec();
+    let new_parent_trees = new_parents
+        .iter()
+        .map(|parent| parent.tree_id().clone())
+        .collect_vec();
+
+    if new_parent_trees == old_parent_trees {
+        Ok((
+            // Optimization: was_empty is only used for newly empty, but when the
+            // parents haven't changed it can't be newly empty.
+            true,
+            // Optimization: Skip merging.
+            old_commit.tree_id().clone(),
+        ))
+    } else {
+        let old_base_tree = merge_commit_trees_without_repo(store, index, &old_parents)?;
+        let new_base_tree = merge_commit_trees_without_repo(store, index, new_parents)?;
+        let old_tree = old_commit.tree()?;
+        Ok((
+            old_base_tree.id() == *old_commit.tree_id(),
+            new_base_tree.merge(&old_base_tree, &old_tree)?.id(),
+        ))
+    }
+}
+
 /// Helps rewrite a commit.
 pub struct CommitRewriter<'repo> {
     mut_repo: &'repo mut MutableRepo,
@@ -198,41 +242,33 @@ impl<'repo> CommitRewriter<'repo> {
         settings: &UserSettings,
         empty: EmptyBehaviour,
     ) -> BackendResult<Option<CommitBuilder<'repo>>> {
-        let old_parents: Vec<_> = self.old_commit.parents().try_collect()?;
-        let old_parent_trees = old_parents
-            .iter()
-            .map(|parent| parent.tree_id().clone())
-            .collect_vec();
         let new_parents: Vec<_> = self
             .new_parents
             .iter()
             .map(|new_parent_id| self.mut_repo.store().get_commit(new_parent_id))
             .try_collect()?;
-        let new_parent_trees = new_parents
-            .iter()
-            .map(|parent| parent.tree_id().clone())
-            .collect_vec();
+        let (was_empty, new_tree_id) = rebase_tree(
+            self.mut_repo.store(),
+            self.mut_repo.index(),
+            &self.old_commit,
+            &new_parents,
+        )?;
+        self.rebase_onto_tree(settings, empty, &new_parents, was_empty, new_tree_id)
+    }
 
-        let (was_empty, new_tree_id) = if new_parent_trees == old_parent_trees {
-            (
-                // Optimization: was_empty is only used for newly empty, but when the
-                // parents haven't changed it can't be newly empty.
-                true,
-                // Optimization: Skip merging.
-                self.old_commit.tree_id().clone(),
-            )
-        } else {
-            let old_base_tree = merge_commit_trees(self.mut_repo, &old_parents)?;
-            let new_base_tree = merge_commit_trees(self.mut_repo, &new_parents)?;
-            let old_tree = self.old_commit.tree()?;
-            (
-                old_base_tree.id() == *self.old_commit.tree_id(),
-                new_base_tree.merge(&old_base_tree, &old_tree)?.id(),
-            )
-        };
+    /// Like [`CommitRewriter::rebase_with_empty_behavior()`], but with the
+    /// result of [`rebase_tree()`] already computed.
+    fn rebase_onto_tree(
+        self,
+        settings: &UserSettings,
+        empty: EmptyBehaviour,
+        new_parents: &[Commit],
+        was_empty: bool,
+        new_tree_id: MergedTreeId,
+    ) -> BackendResult<Option<CommitBuilder<'repo>>> {
         // Ensure we don't abandon commits with multiple parents (merge commits), even
         // if they're empty.
-        if let [parent] = &new_parents[..] {
+        if let [parent] = new_parents {
             let should_abandon = match empty {
                 EmptyBehaviour::Keep => false,
                 EmptyBehaviour::AbandonNewlyEmpty => *parent.tree_id() == new_tree_id && !was_empty,
@@ -292,7 +328,11 @@ pub fn rebase_commit_with_options(
         _ => None,
     };
     let new_parents = rewriter.new_parents.clone();
-    if let Some(builder) = rewriter.rebase_with_empty_behavior(settings, options.empty)? {
+    let old_commit = rewriter.old_commit().clone();
+    if let Some(mut builder) = rewriter.rebase_with_empty_behavior(settings, options.empty)? {
+        if options.keep_author_timestamp {
+            builder = keep_author_timestamp(builder, &old_commit);
+        }
         let new_commit = builder.write()?;
         Ok(RebasedCommit::Rewritten(new_commit))
     } else {
@@ -303,6 +343,16 @@ pub fn rebase_commit_with_options(
     }
 }
 
+/// Sets the author timestamp of `builder` back to the one of `old_commit`.
+fn keep_author_timestamp<'repo>(
+    builder: CommitBuilder<'repo>,
+    old_commit: &Commit,
+) -> CommitBuilder<'repo> {
+    let mut author = builder.author().clone();
+    author.timestamp = old_commit.author().timestamp.clone();
+    builder.set_author(author)
+}
+
 pub fn rebase_to_dest_parent(
     repo: &dyn Repo,
     source: &Commit,
@@ -346,6 +396,9 @@ pub struct RebaseOptions {
     /// If a merge commit would end up with one parent being an ancestor of the
     /// other, then filter out the ancestor.
     pub simplify_ancestor_merge: bool,
+    /// Keep the author timestamp of rewritten commits, even for discardable
+    /// commits, whose author timestamp is otherwise reset.
+    pub keep_author_timestamp: bool,
 }
 
 pub(crate) struct DescendantRebaser<'settings, 'repo> {
@@ -419,3 +472,648 @@ impl<'settings, 'repo> DescendantRebaser<'settings, 'repo> {
         self.mut_repo.update_rewritten_references(self.settings)
     }
 }
+
+/// A callback for getting progress updates while executing a [`RebasePlan`].
+/// It's called with the position of the commit being visited, starting at 1,
+/// and the total number of commits to visit.
+pub type RebaseProgress<'a> = dyn Fn(usize, usize) + 'a;
+
+/// Statistics from applying a [`RebasePlan`].
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct MoveCommitsStats {
+    /// The number of commits in the target set which were rebased.
+    pub num_rebased_targets: u32,
+    /// The number of descendant commits which were rebased.
+    pub num_rebased_descendants: u32,
+    /// The number of commits for which rebase was skipped, due to the commit
+    /// already being in place.
+    pub num_skipped_rebases: u32,
+    /// The number of commits in the target set which were abandoned because
+    /// they became empty.
+    pub num_abandoned: u32,
+    /// The number of descendant commits which were abandoned because they
+    /// became empty.
+    pub num_abandoned_descendants: u32,
+    /// The number of rebased commits which have conflicts, but didn't have
+    /// conflicts before the rebase.
+    pub num_newly_conflicted: u32,
+    /// Maps each rebased commit to its new commit, or to `None` if it was
+    /// abandoned.
+    pub rebased_commits: HashMap<CommitId, Option<CommitId>>,
+}
+
+/// The new parents of every commit affected by moving a set of target commits
+/// in between `new_parent_ids` and `new_children`.
+///
+/// Computing the plan doesn't modify the repo, so the caller can inspect it
+/// before applying it with [`RebasePlan::execute()`].
+#[derive(Clone, Default, Debug)]
+pub struct RebasePlan {
+    /// The commits being moved.
+    pub target_commit_ids: HashSet<CommitId>,
+    /// The roots of the target set, which are rebased onto the new parents.
+    pub target_roots: HashSet<CommitId>,
+    /// The new parents of the commits which become children of the target
+    /// set.
+    pub new_children_parents: HashMap<CommitId, Vec<CommitId>>,
+    /// The commits to visit, parents before children, along with their new
+    /// parents. Includes the target commits, the new children, and their
+    /// descendants.
+    pub to_visit_commits_new_parents: IndexMap<CommitId, (Commit, Vec<CommitId>)>,
+    /// New descriptions to set on the rewritten commits. A commit with a new
+    /// description is rewritten even if its parents don't change.
+    pub new_descriptions: HashMap<CommitId, String>,
+    /// How to handle commits in the target set which become empty.
+    pub empty_targets: EmptyBehaviour,
+    /// How to handle descendants of the target set which become empty. Keeps
+    /// them by default.
+    pub empty_descendants: EmptyBehaviour,
+    /// Keep the author timestamp of the rewritten commits.
+    pub keep_author_timestamp: bool,
+}
+
+impl RebasePlan {
+    /// Computes how to move `target_commits` onto `new_parent_ids`, inserting
+    /// them before `new_children`.
+    pub fn new(
+        repo: &dyn Repo,
+        new_parent_ids: &[CommitId],
+        new_children: &[Commit],
+        target_commits: &[Commit],
+    ) -> BackendResult<Self> {
+        Self::new_impl(
+            repo,
+            new_parent_ids,
+            new_children,
+            target_commits,
+            false,
+            false,
+            None,
+        )
+    }
+
+    /// Like [`RebasePlan::new()`], but `target_commits` must contain all their
+    /// own descendants, in the order returned by evaluating `roots::`. This
+    /// avoids evaluating revsets again to find the connected target set and
+    /// the commits to visit.
+    pub fn new_for_descendants(
+        repo: &dyn Repo,
+        new_parent_ids: &[CommitId],
+        new_children: &[Commit],
+        target_commits: &[Commit],
+    ) -> BackendResult<Self> {
+        Self::new_impl(
+            repo,
+            new_parent_ids,
+            new_children,
+            target_commits,
+            true,
+            false,
+            None,
+        )
+    }
+
+    /// Like [`RebasePlan::new()`], but moves each of `target_commits` onto its
+    /// own new parents from `target_new_parent_ids`, which must have an entry
+    /// for every target commit. The new parents may include other target
+    /// commits, but must not form a loop.
+    pub fn new_with_target_parents(
+        repo: &dyn Repo,
+        target_commits: &[Commit],
+        target_new_parent_ids: &HashMap<CommitId, Vec<CommitId>>,
+    ) -> BackendResult<Self> {
+        Self::new_impl(
+            repo,
+            &[],
+            &[],
+            target_commits,
+            false,
+            false,
+            Some(target_new_parent_ids),
+        )
+    }
+
+    /// Like [`RebasePlan::new()`], but the descendants of `target_commits`
+    /// which aren't targets themselves stay attached to them and are moved
+    /// along, instead of being rebased onto the parents of the target commits.
+    /// None of `new_parent_ids` may be such a descendant.
+    pub fn new_moving_descendants(
+        repo: &dyn Repo,
+        new_parent_ids: &[CommitId],
+        new_children: &[Commit],
+        target_commits: &[Commit],
+    ) -> BackendResult<Self> {
+        Self::new_impl(
+            repo,
+            new_parent_ids,
+            new_children,
+            target_commits,
+            false,
+            true,
+            None,
+        )
+    }
+
+    fn new_impl(
+        repo: &dyn Repo,
+        new_parent_ids: &[CommitId],
+        new_children: &[Commit],
+        target_commits: &[Commit],
+        targets_include_descendants: bool,
+        descendants_follow_targets: bool,
+        target_new_parent_ids: Option<&HashMap<CommitId, Vec<CommitId>>>,
+    ) -> BackendResult<Self> {
+        if target_commits.is_empty() {
+            return Ok(RebasePlan::default());
+        }
+
+        let target_commit_ids: HashSet<_> = target_commits.iter().ids().cloned().collect();
+
+        // A set which contains its own descendants is already connected.
+        let connected_target_commits: Vec<_> = if targets_include_descendants {
+            target_commits.to_vec()
+        } else {
+            RevsetExpression::commits(target_commits.iter().ids().cloned().collect_vec())
+                .connected()
+                .evaluate_programmatic(repo)
+                .map_err(expect_store_error)?
+                .iter()
+                .commits(repo.store())
+                .try_collect()?
+        };
+
+        // Commits in the target set should only have other commits in the set as
+        // parents, except the roots of the set, which persist their original
+        // parents.
+        // If a commit in the set has a parent which is not in the set, but has
+        // an ancestor which is in the set, then the commit will have that ancestor
+        // as a parent.
+        let mut target_commits_internal_parents: HashMap<CommitId, Vec<CommitId>> = HashMap::new();
+        for commit in connected_target_commits.iter().rev() {
+            // The roots of the set will not have any parents found in `new_target_parents`,
+            // and will be stored in `new_target_parents` as an empty vector.
+            let mut new_parents = vec![];
+            for old_parent in commit.parent_ids() {
+                if target_commit_ids.contains(old_parent) {
+                    new_parents.push(old_parent.clone());
+                } else if let Some(parents) = target_commits_internal_parents.get(old_parent) {
+                    new_parents.extend(parents.iter().cloned());
+                }
+            }
+            target_commits_internal_parents.insert(commit.id().clone(), new_parents);
+        }
+        target_commits_internal_parents.retain(|id, _| target_commit_ids.contains(id));
+
+        // Compute the roots of `target_commits`.
+        let target_roots: HashSet<_> = target_commits_internal_parents
+            .iter()
+            .filter(|(_, parents)| parents.is_empty())
+            .map(|(commit_id, _)| commit_id.clone())
+            .collect();
+
+        // If a commit outside the target set has a commit in the target set as a
+        // parent, then - after the transformation - it should have that commit's
+        // ancestors which are not in the target set as parents.
+        let mut target_commits_external_parents: HashMap<CommitId, IndexSet<CommitId>> =
+            HashMap::new();
+        for commit in target_commits.iter().rev() {
+            let mut new_parents = IndexSet::new();
+            for old_parent in commit.parent_ids() {
+                if let Some(parents) = target_commits_external_parents.get(old_parent) {
+                    new_parents.extend(parents.iter().cloned());
+                } else {
+                    new_parents.insert(old_parent.clone());
+                }
+            }
+            target_commits_external_parents.insert(commit.id().clone(), new_parents);
+        }
+
+        // If the new parents include a commit in the target set, replace it with the
+        // commit's ancestors which are outside the set.
+        // e.g. `jj rebase -r A --before A`
+        let new_parent_ids: Vec<_> = new_parent_ids
+            .iter()
+            .flat_map(|parent_id| {
+                if let Some(parent_ids) = target_commits_external_parents.get(parent_id) {
+                    parent_ids.iter().cloned().collect_vec()
+                } else {
+                    [parent_id.clone()].to_vec()
+                }
+            })
+            .collect();
+
+        // If the new children include a commit in the target set, replace it with the
+        // commit's descendants which are outside the set.
+        // e.g. `jj rebase -r A --after A`
+        let new_children: Vec<_> = if new_children
+            .iter()
+            .any(|child| target_commit_ids.contains(child.id()))
+        {
+            let target_commits_descendants: Vec<_> = if targets_include_descendants {
+                target_commits.to_vec()
+            } else {
+                RevsetExpression::commits(target_commit_ids.iter().cloned().collect_vec())
+                    .union(
+                        &RevsetExpression::commits(target_commit_ids.iter().cloned().collect_vec())
+                            .children(),
+                    )
+                    .evaluate_programmatic(repo)
+                    .map_err(expect_store_error)?
+                    .iter()
+                    .commits(repo.store())
+                    .try_collect()?
+            };
+
+            // For all commits in the target set, compute its transitive descendant commits
+            // which are outside of the target set by up to 1 generation.
+            let mut target_commit_external_descendants: HashMap<CommitId, IndexSet<Commit>> =
+                HashMap::new();
+            // Iterate through all descendants of the target set, going through children
+            // before parents.
+            for commit in target_commits_descendants.iter() {
+                if !target_commit_external_descendants.contains_key(commit.id()) {
+                    let children = if target_commit_ids.contains(commit.id()) {
+                        IndexSet::new()
+                    } else {
+                        IndexSet::from([commit.clone()])
+                    };
+                    target_commit_external_descendants.insert(commit.id().clone(), children);
+                }
+
+                let children = target_commit_external_descendants
+                    .get(commit.id())
+                    .unwrap()
+                    .iter()
+                    .cloned()
+                    .collect_vec();
+                for parent_id in commit.parent_ids() {
+                    if target_commit_ids.contains(parent_id) {
+                        if let Some(target_children) =
+                            target_commit_external_descendants.get_mut(parent_id)
+                        {
+                            target_children.extend(children.iter().cloned());
+                        } else {
+                            target_commit_external_descendants
+                                .insert(parent_id.clone(), children.iter().cloned().collect());
+                        }
+                    };
+                }
+            }
+
+            new_children
+                .iter()
+                .flat_map(|child| {
+                    if let Some(children) = target_commit_external_descendants.get(child.id()) {
+                        children.iter().cloned().collect_vec()
+                    } else {
+                        [child.clone()].to_vec()
+                    }
+                })
+                .collect()
+        } else {
+            new_children.to_vec()
+        };
+
+        // Compute the parents of the new children, which will include the heads of the
+        // target set.
+        let new_children_parents: HashMap<_, _> = if !new_children.is_empty() {
+            // Compute the heads of the target set, which will be used as the parents of
+            // `new_children`.
+            let mut target_heads: HashSet<CommitId> = HashSet::new();
+            for commit in connected_target_commits.iter().rev() {
+                target_heads.insert(commit.id().clone());
+                for old_parent in commit.parent_ids() {
+                    target_heads.remove(old_parent);
+                }
+            }
+            let target_heads = connected_target_commits
+                .iter()
+                .rev()
+                .filter(|commit| {
+                    target_heads.contains(commit.id()) && target_commit_ids.contains(commit.id())
+                })
+                .map(|commit| commit.id().clone())
+                .collect_vec();
+
+            new_children
+                .iter()
+                .map(|child_commit| {
+                    let mut new_child_parent_ids = IndexSet::new();
+                    for id in child_commit
+                        .parent_ids()
+                        .iter()
+                        // Replace target commits with their parents outside the target set.
+                        .flat_map(|id| {
+                            if let Some(parents) = target_commits_external_parents.get(id) {
+                                parents.iter().cloned().collect_vec()
+                            } else {
+                                [id.clone()].to_vec()
+                            }
+                        })
+                    {
+                        // Replace any of the new parents of the target commits with
+                        // `target_heads`, since we are "inserting" the target commits in between
+                        // the new parents and the new children. Their ancestors are replaced too,
+                        // since the new children descend from them through the target commits
+                        // anyway. The target heads take the place of the first replaced parent,
+                        // so that the order of the other parents is kept.
+                        if new_parent_ids
+                            .iter()
+                            .any(|new_parent_id| repo.index().is_ancestor(&id, new_parent_id))
+                        {
+                            new_child_parent_ids.extend(target_heads.iter().cloned());
+                        } else {
+                            new_child_parent_ids.insert(id);
+                        }
+                    }
+
+                    // If no parent was replaced, add `target_heads` as the last parents of the
+                    // new child commit.
+                    new_child_parent_ids.extend(target_heads.iter().cloned());
+
+                    (
+                        child_commit.id().clone(),
+                        new_child_parent_ids.iter().cloned().collect_vec(),
+                    )
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        // Compute the set of commits to visit, which includes the target commits, the
+        // new children commits (if any), and their descendants.
+        let to_visit: Vec<_> = if targets_include_descendants && new_children.is_empty() {
+            target_commits.to_vec()
+        } else {
+            let mut roots = target_roots.iter().cloned().collect_vec();
+            roots.extend(new_children.iter().ids().cloned());
+            RevsetExpression::commits(roots)
+                .descendants()
+                .evaluate_programmatic(repo)
+                .map_err(expect_store_error)?
+                .iter()
+                .commits(repo.store())
+                .try_collect()?
+        };
+        let mut to_visit_commits: IndexMap<_, _> = to_visit
+            .into_iter()
+            .map(|commit| (commit.id().clone(), commit))
+            .collect();
+
+        let mut to_visit_commits_new_parents: HashMap<_, _> = to_visit_commits
+            .iter()
+            .map(|(commit_id, commit)| {
+                let new_parents =
+                // New child of the rebased target commits.
+                if let Some(new_child_parents) = new_children_parents.get(commit_id) {
+                    new_child_parents.clone()
+                }
+                // Target commits whose new parents were given explicitly.
+                else if let Some(parent_ids) =
+                    target_new_parent_ids.and_then(|parent_ids| parent_ids.get(commit_id))
+                {
+                    parent_ids.clone()
+                }
+                // Commits in the target set should persist only rebased parents from the target
+                // sets.
+                else if let Some(target_commit_parents) =
+                    target_commits_internal_parents.get(commit_id)
+                {
+                    // If the commit does not have any parents in the target set, it is one of the
+                    // commits in the root set, and should be rebased onto the new destination.
+                    if target_commit_parents.is_empty() {
+                        new_parent_ids.clone()
+                    } else {
+                        target_commit_parents.clone()
+                    }
+                }
+                // Commits outside the target set should have references to commits inside the set
+                // replaced, unless they are moved along with the target commits.
+                else if !descendants_follow_targets
+                    && commit
+                    .parent_ids()
+                    .iter()
+                    .any(|id| target_commits_external_parents.contains_key(id))
+                {
+                    let mut new_parents = vec![];
+                    for parent in commit.parent_ids() {
+                        if let Some(parents) = target_commits_external_parents.get(parent) {
+                            new_parents.extend(parents.iter().cloned());
+                        } else {
+                            new_parents.push(parent.clone());
+                        }
+                    }
+                    new_parents
+                } else {
+                    commit.parent_ids().iter().cloned().collect_vec()
+                };
+
+                (commit_id.clone(), new_parents)
+            })
+            .collect();
+
+        // Re-compute the order of commits to visit, such that each commit's new parents
+        // must be visited first.
+        let mut visited: HashSet<CommitId> = HashSet::new();
+        let to_visit = dag_walk::topo_order_reverse(
+            to_visit_commits.keys().cloned().collect_vec(),
+            |commit_id| commit_id.clone(),
+            |commit_id| -> Vec<CommitId> {
+                visited.insert(commit_id.clone());
+                to_visit_commits_new_parents
+                    .get(commit_id)
+                    .cloned()
+                    .unwrap()
+                    .iter()
+                    // Only add parents which are in the set to be visited and have not already been
+                    // visited.
+                    .filter(|&id| to_visit_commits.contains_key(id) && !visited.contains(id))
+                    .cloned()
+                    .collect()
+            },
+        );
+        let to_visit_commits_new_parents = to_visit
+            .into_iter()
+            .rev()
+            .map(|commit_id| {
+                let commit = to_visit_commits.swap_remove(&commit_id).unwrap();
+                let new_parents = to_visit_commits_new_parents.remove(&commit_id).unwrap();
+                (commit_id, (commit, new_parents))
+            })
+            .collect();
+
+        Ok(RebasePlan {
+            target_commit_ids,
+            target_roots,
+            new_children_parents,
+            to_visit_commits_new_parents,
+            new_descriptions: HashMap::new(),
+            empty_targets: EmptyBehaviour::Keep,
+            empty_descendants: EmptyBehaviour::Keep,
+            keep_author_timestamp: false,
+        })
+    }
+
+    /// Groups the commits to visit into layers, such that the new parents of
+    /// each commit are in earlier layers. Commits within a layer keep their
+    /// order in the plan.
+    fn independent_layers(&self) -> Vec<Vec<&CommitId>> {
+        let mut depths: HashMap<&CommitId, usize> = HashMap::new();
+        let mut layers: Vec<Vec<&CommitId>> = vec![];
+        for (commit_id, (_, parent_ids)) in &self.to_visit_commits_new_parents {
+            let depth = parent_ids
+                .iter()
+                .filter_map(|parent_id| depths.get(parent_id))
+                .map(|parent_depth| parent_depth + 1)
+                .max()
+                .unwrap_or(0);
+            depths.insert(commit_id, depth);
+            if depth == layers.len() {
+                layers.push(vec![]);
+            }
+            layers[depth].push(commit_id);
+        }
+        layers
+    }
+
+    /// Rebases each commit in the plan onto its new parents, and updates the
+    /// references to the rewritten commits.
+    pub fn execute(
+        &self,
+        settings: &UserSettings,
+        mut_repo: &mut MutableRepo,
+    ) -> BackendResult<MoveCommitsStats> {
+        self.execute_with_progress(settings, mut_repo, None)
+    }
+
+    /// Like [`RebasePlan::execute()`], but calls `progress` before visiting
+    /// each commit.
+    pub fn execute_with_progress(
+        &self,
+        settings: &UserSettings,
+        mut_repo: &mut MutableRepo,
+        progress: Option<&RebaseProgress>,
+    ) -> BackendResult<MoveCommitsStats> {
+        let mut stats = MoveCommitsStats::default();
+        if self.to_visit_commits_new_parents.is_empty() {
+            return Ok(stats);
+        }
+
+        let num_to_visit = self.to_visit_commits_new_parents.len();
+        let mut num_visited = 0;
+        for layer in self.independent_layers() {
+            // The new parents of a layer are all in earlier layers, which have
+            // already been written.
+            let layer_new_parent_ids = layer
+                .iter()
+                .map(|old_commit_id| {
+                    let (_, parent_ids) = &self.to_visit_commits_new_parents[*old_commit_id];
+                    mut_repo.new_parents(parent_ids.clone())
+                })
+                .collect_vec();
+            // Merging the trees is the expensive part, and doesn't depend on
+            // other commits in the same layer, so do it in parallel.
+            let store = mut_repo.store();
+            let index = mut_repo.index();
+            let layer_trees: Vec<Option<(Vec<Commit>, bool, MergedTreeId)>> = layer
+                .par_iter()
+                .zip(layer_new_parent_ids.par_iter())
+                .map(|(old_commit_id, new_parent_ids)| -> BackendResult<_> {
+                    let (old_commit, _) = &self.to_visit_commits_new_parents[*old_commit_id];
+                    if *new_parent_ids == old_commit.parent_ids()
+                        && !self.new_descriptions.contains_key(*old_commit_id)
+                    {
+                        return Ok(None);
+                    }
+                    let new_parents: Vec<_> = new_parent_ids
+                        .iter()
+                        .map(|new_parent_id| store.get_commit(new_parent_id))
+                        .try_collect()?;
+                    let (was_empty, new_tree_id) =
+                        rebase_tree(store, index, old_commit, &new_parents)?;
+                    Ok(Some((new_parents, was_empty, new_tree_id)))
+                })
+                .collect::<BackendResult<_>>()?;
+
+            for ((old_commit_id, new_parent_ids), rebased_tree) in
+                layer.into_iter().zip(layer_new_parent_ids).zip(layer_trees)
+            {
+                num_visited += 1;
+                if let Some(progress) = progress {
+                    progress(num_visited, num_to_visit);
+                }
+                let Some((new_parents, was_empty, new_tree_id)) = rebased_tree else {
+                    stats.num_skipped_rebases += 1;
+                    continue;
+                };
+                let (old_commit, _) = &self.to_visit_commits_new_parents[old_commit_id];
+                let rewriter = CommitRewriter::new(mut_repo, old_commit.clone(), new_parent_ids);
+                let new_description = self.new_descriptions.get(old_commit_id);
+                let is_target = self.target_commit_ids.contains(old_commit_id);
+                let empty = if is_target {
+                    self.empty_targets
+                } else {
+                    self.empty_descendants
+                };
+                let Some(mut builder) = rewriter.rebase_onto_tree(
+                    settings,
+                    empty,
+                    &new_parents,
+                    was_empty,
+                    new_tree_id,
+                )?
+                else {
+                    if is_target {
+                        stats.num_abandoned += 1;
+                    } else {
+                        stats.num_abandoned_descendants += 1;
+                    }
+                    stats.rebased_commits.insert(old_commit_id.clone(), None);
+                    continue;
+                };
+                if let Some(description) = new_description {
+                    builder = builder.set_description(description);
+                }
+                if self.keep_author_timestamp {
+                    builder = keep_author_timestamp(builder, old_commit);
+                }
+                let new_commit = builder.write()?;
+                if new_commit.has_conflict()? && !old_commit.has_conflict()? {
+                    stats.num_newly_conflicted += 1;
+                }
+                stats
+                    .rebased_commits
+                    .insert(old_commit_id.clone(), Some(new_commit.id().clone()));
+                if is_target {
+                    stats.num_rebased_targets += 1;
+                } else {
+                    stats.num_rebased_descendants += 1;
+                }
+            }
+        }
+        mut_repo.update_rewritten_references(settings)?;
+
+        Ok(stats)
+    }
+}
+
+/// Moves `target_commits` onto `new_parent_ids`, inserting them before
+/// `new_children`. Shorthand for planning and executing a [`RebasePlan`].
+pub fn move_commits(
+    settings: &UserSettings,
+    mut_repo: &mut MutableRepo,
+    new_parent_ids: &[CommitId],
+    new_children: &[Commit],
+    target_commits: &[Commit],
+) -> BackendResult<MoveCommitsStats> {
+    RebasePlan::new(mut_repo, new_parent_ids, new_children, target_commits)?
+        .execute(settings, mut_repo)
+}
+
+fn expect_store_error(err: RevsetEvaluationError) -> BackendError {
+    match err {
+        RevsetEvaluationError::StoreError(err) => err,
+        RevsetEvaluationError::Other(_) => panic!("Unexpected revset error: {err}"),
+    }
+}
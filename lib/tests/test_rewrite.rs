@@ -12,15 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
 use itertools::Itertools as _;
+use jj_lib::backend::CommitId;
 use jj_lib::commit::Commit;
 use jj_lib::matchers::{EverythingMatcher, FilesMatcher};
 use jj_lib::merged_tree::MergedTree;
 use jj_lib::op_store::{RefTarget, RemoteRef, RemoteRefState, WorkspaceId};
 use jj_lib::repo::Repo;
-use jj_lib::repo_path::RepoPath;
+use jj_lib::repo_path::{RepoPath, RepoPathBuf};
+use jj_lib::revset::{RevsetExpression, RevsetIteratorExt};
 use jj_lib::rewrite::{
-    rebase_commit_with_options, restore_tree, CommitRewriter, EmptyBehaviour, RebaseOptions,
+    move_commits, rebase_commit_with_options, restore_tree, CommitRewriter, EmptyBehaviour,
+    MoveCommitsStats, RebaseOptions, RebasePlan,
 };
 use maplit::{hashmap, hashset};
 use test_case::test_case;
@@ -1605,6 +1611,7 @@ fn test_empty_commit_option(empty_behavior: EmptyBehaviour) {
             RebaseOptions {
                 empty: empty_behavior,
                 simplify_ancestor_merge: true,
+                keep_author_timestamp: false,
             },
         )
         .unwrap();
@@ -1739,6 +1746,7 @@ fn test_rebase_abandoning_empty() {
     let rebase_options = RebaseOptions {
         empty: EmptyBehaviour::AbandonAllEmpty,
         simplify_ancestor_merge: true,
+        keep_author_timestamp: false,
     };
     let rewriter = CommitRewriter::new(tx.mut_repo(), commit_b, vec![commit_b2.id().clone()]);
     rebase_commit_with_options(&settings, rewriter, &rebase_options).unwrap();
@@ -1769,3 +1777,516 @@ fn test_rebase_abandoning_empty() {
         hashset! {new_commit_f.id().clone(), new_wc_commit_id.clone()}
     );
 }
+
+#[test]
+fn test_rebase_plan() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Commit B is moved onto D. Commit C should be rebased onto A.
+    //
+    // C
+    // B D
+    // |/
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_a]);
+
+    let plan = RebasePlan::new(
+        tx.mut_repo(),
+        &[commit_d.id().clone()],
+        &[],
+        &[commit_b.clone()],
+    )
+    .unwrap();
+    assert_eq!(plan.target_commit_ids, hashset! {commit_b.id().clone()});
+    assert_eq!(plan.target_roots, hashset! {commit_b.id().clone()});
+    assert!(plan.new_children_parents.is_empty());
+    let new_parents = plan
+        .to_visit_commits_new_parents
+        .iter()
+        .map(|(id, (_, parent_ids))| (id.clone(), parent_ids.clone()))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(
+        new_parents,
+        hashmap! {
+            commit_b.id().clone() => vec![commit_d.id().clone()],
+            commit_c.id().clone() => vec![commit_a.id().clone()],
+        }
+    );
+    // Planning doesn't rewrite anything.
+    assert_eq!(
+        *tx.mut_repo().view().heads(),
+        hashset! {commit_c.id().clone(), commit_d.id().clone()}
+    );
+
+    let progress_calls = RefCell::new(vec![]);
+    let progress =
+        |current: usize, total: usize| progress_calls.borrow_mut().push((current, total));
+    let stats = plan
+        .execute_with_progress(&settings, tx.mut_repo(), Some(&progress))
+        .unwrap();
+    assert_eq!(*progress_calls.borrow(), vec![(1, 2), (2, 2)]);
+    assert_eq!(stats.num_rebased_targets, 1);
+    assert_eq!(stats.num_rebased_descendants, 1);
+    assert_eq!(stats.num_skipped_rebases, 0);
+    assert_eq!(stats.num_abandoned, 0);
+    assert_eq!(stats.num_abandoned_descendants, 0);
+    assert_eq!(stats.num_newly_conflicted, 0);
+    assert_eq!(
+        stats.rebased_commits.keys().collect::<HashSet<_>>(),
+        hashset! {commit_b.id(), commit_c.id()}
+    );
+    let mut_repo = tx.mut_repo();
+    let new_heads_parents: HashMap<_, _> = mut_repo
+        .view()
+        .heads()
+        .iter()
+        .map(|id| {
+            let commit = mut_repo.store().get_commit(id).unwrap();
+            (commit.change_id().clone(), commit.parent_ids().to_vec())
+        })
+        .collect();
+    assert_eq!(
+        new_heads_parents,
+        hashmap! {
+            commit_b.change_id().clone() => vec![commit_d.id().clone()],
+            commit_c.change_id().clone() => vec![commit_a.id().clone()],
+        }
+    );
+}
+
+#[test]
+fn test_move_commits_insert_before_self() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Commit B is inserted between its parent and itself, as with
+    // `jj rebase -r B --before B`. Nothing should change.
+    //
+    // C
+    // B
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+
+    let stats = move_commits(
+        &settings,
+        tx.mut_repo(),
+        &[commit_a.id().clone()],
+        &[commit_b.clone()],
+        &[commit_b.clone()],
+    )
+    .unwrap();
+    assert_eq!(stats.num_rebased_targets, 0);
+    assert_eq!(stats.num_rebased_descendants, 0);
+    assert_eq!(stats.num_skipped_rebases, 2);
+    assert!(stats.rebased_commits.is_empty());
+    assert_eq!(
+        *tx.mut_repo().view().heads(),
+        hashset! {commit_c.id().clone()}
+    );
+}
+
+#[test]
+fn test_move_commits_insert_after_self() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Commit B is inserted between itself and its child, as with
+    // `jj rebase -r B --after B`. Nothing should change.
+    //
+    // C
+    // B
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+
+    let stats = move_commits(
+        &settings,
+        tx.mut_repo(),
+        &[commit_b.id().clone()],
+        &[commit_c.clone()],
+        &[commit_b.clone()],
+    )
+    .unwrap();
+    assert_eq!(stats.num_rebased_targets, 0);
+    assert_eq!(stats.num_rebased_descendants, 0);
+    assert_eq!(stats.num_skipped_rebases, 2);
+    assert!(stats.rebased_commits.is_empty());
+    assert_eq!(
+        *tx.mut_repo().view().heads(),
+        hashset! {commit_c.id().clone()}
+    );
+}
+
+#[test]
+fn test_move_commits_after_child() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Commit B is moved onto its child C, as with `jj rebase -r B --after C`.
+    // Commit C should be rebased onto A.
+    //
+    // C
+    // B
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+
+    let stats = move_commits(
+        &settings,
+        tx.mut_repo(),
+        &[commit_c.id().clone()],
+        &[],
+        &[commit_b.clone()],
+    )
+    .unwrap();
+    assert_eq!(stats.num_rebased_targets, 1);
+    assert_eq!(stats.num_rebased_descendants, 1);
+    assert_eq!(stats.num_skipped_rebases, 0);
+    let rebase_map = rebased_commit_map(&stats);
+    assert_eq!(rebase_map.len(), 2);
+    let new_commit_c = assert_rebased_onto(tx.mut_repo(), &rebase_map, &commit_c, &[commit_a.id()]);
+    let new_commit_b =
+        assert_rebased_onto(tx.mut_repo(), &rebase_map, &commit_b, &[new_commit_c.id()]);
+    assert_eq!(
+        *tx.mut_repo().view().heads(),
+        hashset! {new_commit_b.id().clone()}
+    );
+}
+
+#[test]
+fn test_move_commits_before_drops_ancestor_parents() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Commit C is inserted onto A and before B and D. Commit D should not keep
+    // the root commit as a parent, since it's an ancestor of A.
+    //
+    // B
+    // A C D
+    // |/ /
+    // root
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.initial_commit();
+    let commit_d = graph_builder.initial_commit();
+
+    let stats = move_commits(
+        &settings,
+        tx.mut_repo(),
+        &[commit_a.id().clone()],
+        &[commit_b.clone(), commit_d.clone()],
+        &[commit_c.clone()],
+    )
+    .unwrap();
+    assert_eq!(stats.num_rebased_targets, 1);
+    assert_eq!(stats.num_rebased_descendants, 2);
+    let rebase_map = rebased_commit_map(&stats);
+    let new_commit_c = assert_rebased_onto(tx.mut_repo(), &rebase_map, &commit_c, &[commit_a.id()]);
+    let new_commit_b =
+        assert_rebased_onto(tx.mut_repo(), &rebase_map, &commit_b, &[new_commit_c.id()]);
+    let new_commit_d =
+        assert_rebased_onto(tx.mut_repo(), &rebase_map, &commit_d, &[new_commit_c.id()]);
+    assert_eq!(
+        *tx.mut_repo().view().heads(),
+        hashset! {new_commit_b.id().clone(), new_commit_d.id().clone()}
+    );
+}
+
+#[test]
+fn test_move_commits_wide_fan_out() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Commit A is moved onto B, as with `jj rebase -s A -d B`. The many
+    // children of A are independent of each other, so their trees can be
+    // merged concurrently, but the result should be the same as rebasing them
+    // one at a time.
+    //
+    // C1 C2 ... Cn
+    //  \ |     /
+    //     A     B
+    //     |    /
+    //     root
+    const NUM_CHILDREN: usize = 100;
+    let mut tx = repo.start_transaction(&settings);
+    let root_commit_id = repo.store().root_commit_id().clone();
+    let path_a = RepoPath::from_internal_string("a");
+    let path_b = RepoPath::from_internal_string("b");
+    let tree_a = create_tree(repo, &[(path_a, "a")]);
+    let tree_b = create_tree(repo, &[(path_b, "b")]);
+    let commit_a = tx
+        .mut_repo()
+        .new_commit(&settings, vec![root_commit_id.clone()], tree_a.id())
+        .write()
+        .unwrap();
+    let commit_b = tx
+        .mut_repo()
+        .new_commit(&settings, vec![root_commit_id], tree_b.id())
+        .write()
+        .unwrap();
+    let child_paths = (0..NUM_CHILDREN)
+        .map(|i| RepoPathBuf::from_internal_string(format!("c{i}")))
+        .collect_vec();
+    let children = child_paths
+        .iter()
+        .map(|path| {
+            let tree = create_tree(repo, &[(path_a, "a"), (path.as_ref(), "c")]);
+            tx.mut_repo()
+                .new_commit(&settings, vec![commit_a.id().clone()], tree.id())
+                .write()
+                .unwrap()
+        })
+        .collect_vec();
+
+    let stats = move_commits(
+        &settings,
+        tx.mut_repo(),
+        &[commit_b.id().clone()],
+        &[],
+        &[commit_a.clone()],
+    )
+    .unwrap();
+    assert_eq!(stats.num_rebased_targets, 1);
+    assert_eq!(stats.num_rebased_descendants, NUM_CHILDREN as u32);
+    assert_eq!(stats.num_skipped_rebases, 0);
+    assert_eq!(stats.num_abandoned, 0);
+    assert_eq!(stats.num_abandoned_descendants, 0);
+    assert_eq!(stats.num_newly_conflicted, 0);
+    let rebase_map = rebased_commit_map(&stats);
+    assert_eq!(rebase_map.len(), NUM_CHILDREN + 1);
+    let new_commit_a = assert_rebased_onto(tx.mut_repo(), &rebase_map, &commit_a, &[commit_b.id()]);
+    let mut new_heads = HashSet::new();
+    for (child, path) in children.iter().zip(&child_paths) {
+        let new_child =
+            assert_rebased_onto(tx.mut_repo(), &rebase_map, child, &[new_commit_a.id()]);
+        let new_tree = new_child.tree().unwrap();
+        assert_eq!(
+            new_tree.path_value(path_a).unwrap(),
+            tree_a.path_value(path_a).unwrap()
+        );
+        assert_eq!(
+            new_tree.path_value(path_b).unwrap(),
+            tree_b.path_value(path_b).unwrap()
+        );
+        assert_eq!(
+            new_tree.path_value(path).unwrap(),
+            child.tree().unwrap().path_value(path).unwrap()
+        );
+        new_heads.insert(new_child.id().clone());
+    }
+    assert_eq!(*tx.mut_repo().view().heads(), new_heads);
+}
+
+#[test]
+fn test_rebase_plan_for_descendants() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Planning with the already-evaluated descendants of B should give the same
+    // plan as evaluating the revsets again.
+    //
+    // E
+    // D F
+    // |/
+    // C
+    // B
+    // A G
+    // |/
+    // root
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_c]);
+    let commit_e = graph_builder.commit_with_parents(&[&commit_d]);
+    let commit_f = graph_builder.commit_with_parents(&[&commit_c]);
+    let commit_g = graph_builder.initial_commit();
+
+    let target_commits: Vec<_> = RevsetExpression::commits(vec![commit_b.id().clone()])
+        .descendants()
+        .evaluate_programmatic(tx.repo())
+        .unwrap()
+        .iter()
+        .commits(repo.store())
+        .try_collect()
+        .unwrap();
+    assert_eq!(target_commits.len(), 5);
+
+    let summarize = |plan: RebasePlan| {
+        (
+            plan.target_commit_ids,
+            plan.target_roots,
+            plan.new_children_parents,
+            plan.to_visit_commits_new_parents
+                .into_iter()
+                .map(|(commit_id, (_, new_parent_ids))| (commit_id, new_parent_ids))
+                .collect_vec(),
+        )
+    };
+    let cases: [(&[CommitId], &[Commit]); 4] = [
+        // Move onto G.
+        (&[commit_g.id().clone()], &[]),
+        // Insert between A and G.
+        (&[commit_a.id().clone()], &[commit_g.clone()]),
+        // Insert between A and C, which is in the target set.
+        (&[commit_a.id().clone()], &[commit_c.clone()]),
+        // Keep in place.
+        (&[commit_a.id().clone()], &[]),
+    ];
+    for (new_parent_ids, new_children) in cases {
+        let plan =
+            RebasePlan::new(tx.repo(), new_parent_ids, new_children, &target_commits).unwrap();
+        let plan_for_descendants = RebasePlan::new_for_descendants(
+            tx.repo(),
+            new_parent_ids,
+            new_children,
+            &target_commits,
+        )
+        .unwrap();
+        assert_eq!(summarize(plan_for_descendants), summarize(plan));
+    }
+
+    let stats =
+        RebasePlan::new_for_descendants(tx.repo(), &[commit_g.id().clone()], &[], &target_commits)
+            .unwrap()
+            .execute(&settings, tx.mut_repo())
+            .unwrap();
+    assert_eq!(stats.num_rebased_targets, 5);
+    assert_eq!(stats.num_rebased_descendants, 0);
+    assert_eq!(stats.num_skipped_rebases, 0);
+    let rebase_map = rebased_commit_map(&stats);
+    assert_rebased_onto(tx.mut_repo(), &rebase_map, &commit_b, &[commit_g.id()]);
+    assert_eq!(
+        *tx.mut_repo().view().heads(),
+        hashset! {
+            rebase_map[commit_e.id()].clone(),
+            rebase_map[commit_f.id()].clone(),
+        }
+    );
+}
+
+#[test]
+fn test_rebase_plan_with_target_parents() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Commits B and C are swapped by giving each of them its own new parents.
+    // Commit D is a child of C outside the target set, so it should be rebased
+    // onto A like with `jj rebase -r`.
+    //
+    // D
+    // C
+    // B
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_c]);
+
+    let target_new_parent_ids = hashmap! {
+        commit_b.id().clone() => vec![commit_c.id().clone()],
+        commit_c.id().clone() => vec![commit_a.id().clone()],
+    };
+    let stats = RebasePlan::new_with_target_parents(
+        tx.repo(),
+        &[commit_c.clone(), commit_b.clone()],
+        &target_new_parent_ids,
+    )
+    .unwrap()
+    .execute(&settings, tx.mut_repo())
+    .unwrap();
+    assert_eq!(stats.num_rebased_targets, 2);
+    assert_eq!(stats.num_rebased_descendants, 1);
+    assert_eq!(stats.num_skipped_rebases, 0);
+    let rebase_map = rebased_commit_map(&stats);
+    assert_eq!(rebase_map.len(), 3);
+    let new_commit_c = assert_rebased_onto(tx.mut_repo(), &rebase_map, &commit_c, &[commit_a.id()]);
+    let new_commit_b =
+        assert_rebased_onto(tx.mut_repo(), &rebase_map, &commit_b, &[new_commit_c.id()]);
+    let new_commit_d = assert_rebased_onto(tx.mut_repo(), &rebase_map, &commit_d, &[commit_a.id()]);
+    assert_eq!(
+        *tx.mut_repo().view().heads(),
+        hashset! {new_commit_b.id().clone(), new_commit_d.id().clone()}
+    );
+}
+
+#[test]
+fn test_rebase_plan_moving_descendants() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Commit B is moved onto D. Its child C stays attached to it instead of
+    // being rebased onto A.
+    //
+    // C
+    // B D
+    // |/
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_a]);
+
+    let stats = RebasePlan::new_moving_descendants(
+        tx.repo(),
+        &[commit_d.id().clone()],
+        &[],
+        &[commit_b.clone()],
+    )
+    .unwrap()
+    .execute(&settings, tx.mut_repo())
+    .unwrap();
+    assert_eq!(stats.num_rebased_targets, 1);
+    assert_eq!(stats.num_rebased_descendants, 1);
+    let rebase_map = rebased_commit_map(&stats);
+    assert_eq!(rebase_map.len(), 2);
+    let new_commit_b = assert_rebased_onto(tx.mut_repo(), &rebase_map, &commit_b, &[commit_d.id()]);
+    let new_commit_c =
+        assert_rebased_onto(tx.mut_repo(), &rebase_map, &commit_c, &[new_commit_b.id()]);
+    assert_eq!(
+        *tx.mut_repo().view().heads(),
+        hashset! {new_commit_c.id().clone()}
+    );
+}
+
+/// Returns the new commit id of each commit which was rewritten, not
+/// abandoned, by a rebase.
+fn rebased_commit_map(stats: &MoveCommitsStats) -> HashMap<CommitId, CommitId> {
+    stats
+        .rebased_commits
+        .iter()
+        .filter_map(|(old_id, new_id)| Some((old_id.clone(), new_id.clone()?)))
+        .collect()
+}